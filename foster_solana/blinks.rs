@@ -2,21 +2,55 @@ use std::collections::HashMap;
 use std::str::FromStr;
 
 use base64::{engine::general_purpose::STANDARD as base64, Engine as _};
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_config::RpcTransactionConfig,
+};
 use solana_sdk::{
-    compute_budget::ComputeBudgetInstruction, message::Message, pubkey::Pubkey, system_instruction,
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_instruction,
     transaction::Transaction,
 };
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::{
+    auction::{
+        parse_place_bid_instruction, parse_settle_auction_instruction, place_bid_instructions,
+        settle_auction_instructions,
+    },
+    get_latest_blockhash, get_rpc_client,
+    marketplace::{
+        accept_offer_instructions, buy_listing_instructions, delist_instructions,
+        get_listing_price, list_instructions, make_offer_instructions,
+        parse_accept_offer_instruction, parse_buy_listing_instruction, parse_delist_instruction,
+        parse_list_instruction, parse_make_offer_instruction,
+    },
+};
 
-use crate::get_latest_blockhash;
+pub const ORDER_MEMO_PREFIX: &str = "foster:order:";
 
 pub async fn create_merch_blink_transaction(
     buyer_address: &str,
+    order_id: i32,
+    reference_address: &str,
     payment_splits: HashMap<String, u64>,
 ) -> Result<String, String> {
-    let buyer = Pubkey::from_str(buyer_address)
-        .map_err(|e| format!("invalid buyer pubkey {buyer_address}: {e}"))?;
+    let buyer = parse_pubkey("buyer", buyer_address)?;
+    let reference = parse_pubkey("reference", reference_address)?;
 
-    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(1_000_000)];
+    // lets the payment be matched to its order from the chain alone
+    let memo = format!("{ORDER_MEMO_PREFIX}{order_id}");
+    let mut instructions = vec![
+        ComputeBudgetInstruction::set_compute_unit_price(1_000_000),
+        spl_memo::build_memo(memo.as_bytes(), &[&buyer]),
+    ];
+    let first_transfer = instructions.len();
     let mut invalid_pubkeys = vec![];
     for (address, lamports) in payment_splits.iter() {
         match Pubkey::from_str(address) {
@@ -26,6 +60,10 @@ pub async fn create_merch_blink_transaction(
             Err(e) => invalid_pubkeys.push(format!("  could not parse {address} as pubkey: {e}")),
         }
     }
+    // solana pay reference, so the payment can be found with getSignaturesForAddress
+    if let Some(transfer) = instructions.get_mut(first_transfer) {
+        transfer.accounts.push(AccountMeta::new_readonly(reference, false));
+    }
 
     if !invalid_pubkeys.is_empty() {
         return Err(format!(
@@ -34,14 +72,546 @@ pub async fn create_merch_blink_transaction(
         ));
     }
 
+    serialize_unsigned_transaction(&instructions, &buyer).await
+}
+
+// compute units of a single edition mint, the default limit only fits one or two
+const PRINT_COMPUTE_UNITS: u32 = 150_000;
+
+// mints every print in one transaction, the edition mints and the print authority sign up front
+pub async fn create_print_transaction(
+    buyer_address: &str,
+    print_instructions: Vec<Vec<Instruction>>,
+    signers: &[Keypair],
+) -> Result<String, String> {
+    let buyer = parse_pubkey("buyer", buyer_address)?;
+
+    let mut instructions = vec![
+        ComputeBudgetInstruction::set_compute_unit_limit(
+            PRINT_COMPUTE_UNITS * print_instructions.len() as u32,
+        ),
+        ComputeBudgetInstruction::set_compute_unit_price(1_000_000),
+    ];
+    instructions.extend(print_instructions.into_iter().flatten());
+
     let (latest_blockhash, _) = get_latest_blockhash().await?;
-    let tx = Transaction::new_unsigned(Message::new_with_blockhash(
+    let mut tx = Transaction::new_unsigned(Message::new_with_blockhash(
         &instructions,
         Some(&buyer),
         &latest_blockhash,
     ));
+    tx.try_partial_sign(&signers.iter().collect::<Vec<_>>(), latest_blockhash)
+        .map_err(|e| format!("could not sign print transaction: {e}"))?;
+
+    serialize_transaction(&tx)
+}
+
+pub async fn create_buy_listing_transaction(
+    buyer_address: &str,
+    seller_address: &str,
+    mint_address: &str,
+    price_lamports: u64,
+) -> Result<String, String> {
+    let buyer = parse_pubkey("buyer", buyer_address)?;
+    let seller = parse_pubkey("seller", seller_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(1_000_000)];
+    instructions.extend(buy_listing_instructions(&buyer, &seller, &mint, price_lamports)?);
+
+    serialize_unsigned_transaction(&instructions, &buyer).await
+}
+
+pub async fn create_bid_transaction(
+    bidder_address: &str,
+    mint_address: &str,
+    bid_lamports: u64,
+) -> Result<String, String> {
+    let bidder = parse_pubkey("bidder", bidder_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(1_000_000)];
+    instructions.extend(place_bid_instructions(&bidder, &mint, bid_lamports)?);
+
+    serialize_unsigned_transaction(&instructions, &bidder).await
+}
+
+// escrows the offered lamports until the owner accepts or the bidder cancels
+pub async fn create_offer_transaction(
+    bidder_address: &str,
+    mint_address: &str,
+    offer_lamports: u64,
+) -> Result<String, String> {
+    let bidder = parse_pubkey("bidder", bidder_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(1_000_000)];
+    instructions.extend(make_offer_instructions(&bidder, &mint, offer_lamports)?);
+
+    serialize_unsigned_transaction(&instructions, &bidder).await
+}
+
+pub async fn create_accept_offer_transaction(
+    owner_address: &str,
+    bidder_address: &str,
+    mint_address: &str,
+    offer_lamports: u64,
+) -> Result<String, String> {
+    let owner = parse_pubkey("owner", owner_address)?;
+    let bidder = parse_pubkey("bidder", bidder_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(1_000_000)];
+    instructions.extend(accept_offer_instructions(&owner, &bidder, &mint, offer_lamports)?);
+
+    serialize_unsigned_transaction(&instructions, &owner).await
+}
+
+// lists the nft, or updates the price of an existing listing
+pub async fn create_list_transaction(
+    owner_address: &str,
+    mint_address: &str,
+    price_lamports: u64,
+) -> Result<String, String> {
+    let owner = parse_pubkey("owner", owner_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(1_000_000)];
+    instructions.extend(list_instructions(&owner, &mint, price_lamports)?);
+
+    serialize_unsigned_transaction(&instructions, &owner).await
+}
+
+pub async fn create_delist_transaction(
+    owner_address: &str,
+    mint_address: &str,
+) -> Result<String, String> {
+    let owner = parse_pubkey("owner", owner_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(1_000_000)];
+    instructions.extend(delist_instructions(&owner, &mint)?);
+
+    serialize_unsigned_transaction(&instructions, &owner).await
+}
+
+pub async fn create_settle_auction_transaction(
+    authority_address: &str,
+    mint_address: &str,
+) -> Result<String, String> {
+    let authority = parse_pubkey("authority", authority_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(1_000_000)];
+    instructions.extend(settle_auction_instructions(&authority, &mint)?);
+
+    serialize_unsigned_transaction(&instructions, &authority).await
+}
+
+pub async fn confirm_transaction(signature: &str) -> Result<(), String> {
+    let signature = Signature::from_str(signature)
+        .map_err(|e| format!("invalid signature {signature}: {e}"))?;
+    let status = get_rpc_client()
+        .get_signature_status_with_commitment(&signature, CommitmentConfig::confirmed())
+        .await
+        .map_err(|e| format!("could not fetch status of {signature}: {e}"))?;
+
+    match status {
+        Some(Ok(())) => Ok(()),
+        Some(Err(e)) => Err(format!("transaction {signature} failed: {e}")),
+        None => Err(format!("transaction {signature} is not confirmed")),
+    }
+}
+
+// instructions of a successful transaction signed by `signer`, with their accounts resolved
+async fn get_signed_instructions(
+    signature: &str,
+    signer: &Pubkey,
+) -> Result<Vec<Instruction>, String> {
+    let signature = Signature::from_str(signature)
+        .map_err(|e| format!("invalid signature {signature}: {e}"))?;
+    let transaction = get_rpc_client()
+        .get_transaction_with_config(
+            &signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await
+        .map_err(|e| format!("could not fetch transaction {signature}: {e}"))?
+        .transaction;
+    if let Some(e) = transaction.meta.and_then(|meta| meta.err) {
+        return Err(format!("transaction {signature} failed: {e}"));
+    }
+    let message = transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| format!("could not decode transaction {signature}"))?
+        .message;
+
+    // blink transactions don't use lookup tables, so every account is a static key
+    let keys = message.static_account_keys();
+    let header = message.header();
+    let signed = header.num_required_signatures as usize;
+    if !keys.iter().take(signed).any(|key| key == signer) {
+        return Err(format!("transaction {signature} was not signed by {signer}"));
+    }
+    let writable_signed = signed - header.num_readonly_signed_accounts as usize;
+    let writable_unsigned = keys.len() - header.num_readonly_unsigned_accounts as usize;
+    let account = |index: u8| {
+        let index = index as usize;
+        let pubkey = *keys
+            .get(index)
+            .ok_or_else(|| format!("transaction {signature} uses lookup tables"))?;
+        Ok::<_, String>(AccountMeta {
+            pubkey,
+            is_signer: index < signed,
+            is_writable: index < writable_signed || (signed..writable_unsigned).contains(&index),
+        })
+    };
+    message
+        .instructions()
+        .iter()
+        .map(|instruction| {
+            Ok(Instruction {
+                program_id: account(instruction.program_id_index)?.pubkey,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|index| account(*index))
+                    .collect::<Result<_, String>>()?,
+                data: instruction.data.clone(),
+            })
+        })
+        .collect()
+}
+
+// checks the transaction paid for by `buyer_address` created every edition mint
+pub async fn confirm_print_transaction(
+    signature: &str,
+    buyer_address: &str,
+    edition_mint_addresses: &[&str],
+) -> Result<(), String> {
+    let buyer = parse_pubkey("buyer", buyer_address)?;
+    let instructions = get_signed_instructions(signature, &buyer).await?;
+
+    for edition_mint_address in edition_mint_addresses {
+        let edition_mint = parse_pubkey("edition mint", edition_mint_address)?;
+        // new mints sign their own creation, other transactions only read or write them
+        let minted = instructions
+            .iter()
+            .flat_map(|instruction| instruction.accounts.iter())
+            .any(|account| account.pubkey == edition_mint && account.is_signer);
+        if !minted {
+            return Err(format!("transaction {signature} did not mint {edition_mint}"));
+        }
+    }
+    Ok(())
+}
+
+// lamports paid by `buyer_address` for the listing of `mint_address`
+pub async fn confirm_buy_listing_transaction(
+    signature: &str,
+    buyer_address: &str,
+    mint_address: &str,
+) -> Result<u64, String> {
+    let buyer = parse_pubkey("buyer", buyer_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    get_signed_instructions(signature, &buyer)
+        .await?
+        .iter()
+        .filter_map(parse_buy_listing_instruction)
+        .find(|(purchase_buyer, _, purchase_mint, _)| {
+            *purchase_buyer == buyer && *purchase_mint == mint
+        })
+        .map(|(_, _, _, price_lamports)| price_lamports)
+        .ok_or_else(|| format!("transaction {signature} did not buy {mint} for {buyer}"))
+}
+
+// lamports bid by `bidder_address` on the auction of `mint_address`
+pub async fn confirm_bid_transaction(
+    signature: &str,
+    bidder_address: &str,
+    mint_address: &str,
+) -> Result<u64, String> {
+    let bidder = parse_pubkey("bidder", bidder_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    get_signed_instructions(signature, &bidder)
+        .await?
+        .iter()
+        .filter_map(parse_place_bid_instruction)
+        .find(|(bid_bidder, bid_mint, _)| *bid_bidder == bidder && *bid_mint == mint)
+        .map(|(_, _, bid_lamports)| bid_lamports)
+        .ok_or_else(|| format!("transaction {signature} is not a bid by {bidder} on {mint}"))
+}
+
+// lamports escrowed by `bidder_address` for an offer on `mint_address`
+pub async fn confirm_offer_transaction(
+    signature: &str,
+    bidder_address: &str,
+    mint_address: &str,
+) -> Result<u64, String> {
+    let bidder = parse_pubkey("bidder", bidder_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    get_signed_instructions(signature, &bidder)
+        .await?
+        .iter()
+        .filter_map(parse_make_offer_instruction)
+        .find(|(offer_bidder, offer_mint, _)| *offer_bidder == bidder && *offer_mint == mint)
+        .map(|(_, _, offer_lamports)| offer_lamports)
+        .ok_or_else(|| format!("transaction {signature} is not an offer by {bidder} on {mint}"))
+}
+
+// lamports released from the escrow of `bidder_address` when the owner accepted their offer
+pub async fn confirm_accept_offer_transaction(
+    signature: &str,
+    owner_address: &str,
+    bidder_address: &str,
+    mint_address: &str,
+) -> Result<u64, String> {
+    let owner = parse_pubkey("owner", owner_address)?;
+    let bidder = parse_pubkey("bidder", bidder_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    get_signed_instructions(signature, &owner)
+        .await?
+        .iter()
+        .filter_map(parse_accept_offer_instruction)
+        .find(|(offer_owner, offer_bidder, offer_mint, _)| {
+            *offer_owner == owner && *offer_bidder == bidder && *offer_mint == mint
+        })
+        .map(|(_, _, _, offer_lamports)| offer_lamports)
+        .ok_or_else(|| {
+            format!("transaction {signature} did not accept the offer of {bidder} on {mint}")
+        })
+}
+
+pub async fn confirm_list_transaction(
+    signature: &str,
+    owner_address: &str,
+    mint_address: &str,
+) -> Result<(), String> {
+    let owner = parse_pubkey("owner", owner_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    get_signed_instructions(signature, &owner)
+        .await?
+        .iter()
+        .filter_map(parse_list_instruction)
+        .find(|(list_owner, list_mint, _)| *list_owner == owner && *list_mint == mint)
+        .map(|_| ())
+        .ok_or_else(|| format!("transaction {signature} did not list {mint} for {owner}"))
+}
+
+pub async fn confirm_delist_transaction(
+    signature: &str,
+    owner_address: &str,
+    mint_address: &str,
+) -> Result<(), String> {
+    let owner = parse_pubkey("owner", owner_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    get_signed_instructions(signature, &owner)
+        .await?
+        .iter()
+        .filter_map(parse_delist_instruction)
+        .find(|(delist_owner, delist_mint)| *delist_owner == owner && *delist_mint == mint)
+        .map(|_| ())
+        .ok_or_else(|| format!("transaction {signature} did not delist {mint} for {owner}"))
+}
+
+pub async fn confirm_settle_auction_transaction(
+    signature: &str,
+    authority_address: &str,
+    mint_address: &str,
+) -> Result<(), String> {
+    let authority = parse_pubkey("authority", authority_address)?;
+    let mint = parse_pubkey("mint", mint_address)?;
+
+    get_signed_instructions(signature, &authority)
+        .await?
+        .iter()
+        .filter_map(parse_settle_auction_instruction)
+        .find(|settle_mint| *settle_mint == mint)
+        .map(|_| ())
+        .ok_or_else(|| format!("transaction {signature} did not settle the auction of {mint}"))
+}
+
+// price of the current marketplace listing of `mint_address`, if it is listed
+pub async fn get_listing_lamports(mint_address: &str) -> Result<Option<u64>, String> {
+    let mint = parse_pubkey("mint", mint_address)?;
+    get_listing_price(&mint).await
+}
+
+// a fresh pubkey per order, nothing is ever signed with it
+pub fn create_payment_reference() -> String {
+    Keypair::new().pubkey().to_string()
+}
+
+// successful transactions that include the reference, oldest first. anyone can add the reference
+// to a transaction, so each one still has to be checked against the order
+pub async fn find_reference_signatures(reference_address: &str) -> Result<Vec<String>, String> {
+    let reference = parse_pubkey("reference", reference_address)?;
+    let signatures = get_rpc_client()
+        .get_signatures_for_address_with_config(
+            &reference,
+            GetConfirmedSignaturesForAddress2Config {
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..GetConfirmedSignaturesForAddress2Config::default()
+            },
+        )
+        .await
+        .map_err(|e| format!("could not fetch signatures of reference {reference}: {e}"))?;
+
+    // signatures are returned newest first
+    Ok(signatures
+        .into_iter()
+        .rev()
+        .filter(|status| status.err.is_none())
+        .map(|status| status.signature)
+        .collect())
+}
+
+// (order id, signature) of recent successful transactions with an order memo, newest first
+pub async fn get_recent_order_payments(
+    address: &str,
+    limit: usize,
+) -> Result<Vec<(i32, String)>, String> {
+    let pubkey = parse_pubkey("address", address)?;
+    let signatures = get_rpc_client()
+        .get_signatures_for_address_with_config(
+            &pubkey,
+            GetConfirmedSignaturesForAddress2Config {
+                limit: Some(limit),
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..GetConfirmedSignaturesForAddress2Config::default()
+            },
+        )
+        .await
+        .map_err(|e| format!("could not fetch signatures of {address}: {e}"))?;
+
+    Ok(signatures
+        .into_iter()
+        .filter(|status| status.err.is_none())
+        .filter_map(|status| Some((parse_order_memo(status.memo.as_deref()?)?, status.signature)))
+        .collect())
+}
+
+// rpc nodes report memos as `[<length>] <memo>`, joined by `; ` when there are several
+fn parse_order_memo(memos: &str) -> Option<i32> {
+    memos.split("; ").find_map(|memo| {
+        let (_, memo) = memo.split_once("] ")?;
+        memo.strip_prefix(ORDER_MEMO_PREFIX)?.parse().ok()
+    })
+}
+
+fn parse_pubkey(name: &str, address: &str) -> Result<Pubkey, String> {
+    Pubkey::from_str(address).map_err(|e| format!("invalid {name} pubkey {address}: {e}"))
+}
+
+async fn serialize_unsigned_transaction(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+) -> Result<String, String> {
+    let (latest_blockhash, _) = get_latest_blockhash().await?;
+    let tx = Transaction::new_unsigned(Message::new_with_blockhash(
+        instructions,
+        Some(payer),
+        &latest_blockhash,
+    ));
+
+    serialize_transaction(&tx)
+}
+
+fn serialize_transaction(tx: &Transaction) -> Result<String, String> {
     let serialized_transaction =
-        bincode::serialize(&tx).map_err(|e| format!("could not serialize transaction: {e}"))?;
+        bincode::serialize(tx).map_err(|e| format!("could not serialize transaction: {e}"))?;
+    // wallets can't send anything bigger, so it's an error here rather than in the wallet
+    if serialized_transaction.len() > PACKET_DATA_SIZE {
+        return Err(format!(
+            "transaction is {} bytes, over the {PACKET_DATA_SIZE} byte limit",
+            serialized_transaction.len()
+        ));
+    }
 
     Ok(base64.encode(serialized_transaction))
 }
+
+pub const MESSAGE_SIGNER_KEY_ENV: &str = "BLINKS_MESSAGE_SIGNER_KEY";
+
+fn get_message_signer() -> Result<Keypair, String> {
+    let key = std::env::var(MESSAGE_SIGNER_KEY_ENV)
+        .map_err(|e| format!("could not read {MESSAGE_SIGNER_KEY_ENV}: {e}"))?;
+    let bytes = bs58::decode(key)
+        .into_vec()
+        .map_err(|e| format!("could not decode {MESSAGE_SIGNER_KEY_ENV}: {e}"))?;
+    Keypair::from_bytes(&bytes).map_err(|e| format!("invalid {MESSAGE_SIGNER_KEY_ENV}: {e}"))
+}
+
+pub fn create_message_nonce() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+// signs the payload with the server key, so it can round trip through the wallet untouched
+pub fn seal_message_state(payload: &[u8]) -> Result<String, String> {
+    let signature = get_message_signer()?.sign_message(payload);
+    Ok(format!("{}.{signature}", base64.encode(payload)))
+}
+
+pub fn open_message_state(state: &str) -> Result<Vec<u8>, String> {
+    let (payload, signature) = state
+        .split_once('.')
+        .ok_or_else(|| "invalid message state".to_string())?;
+    let payload = base64
+        .decode(payload)
+        .map_err(|e| format!("could not decode message state: {e}"))?;
+    let signature = Signature::from_str(signature)
+        .map_err(|e| format!("invalid message state signature: {e}"))?;
+
+    if !signature.verify(get_message_signer()?.pubkey().as_ref(), &payload) {
+        return Err("message state was not issued by this server".to_string());
+    }
+    Ok(payload)
+}
+
+pub fn verify_message_signature(
+    signer_address: &str,
+    message: &str,
+    signature: &str,
+) -> Result<(), String> {
+    let signer = Pubkey::from_str(signer_address)
+        .map_err(|e| format!("invalid signer pubkey {signer_address}: {e}"))?;
+    let signature =
+        Signature::from_str(signature).map_err(|e| format!("invalid signature {signature}: {e}"))?;
+
+    if !signature.verify(signer.as_ref(), message.as_bytes()) {
+        return Err(format!("message was not signed by {signer_address}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_order_memo_single() {
+        assert_eq!(parse_order_memo("[17] foster:order:42"), Some(42));
+        assert_eq!(parse_order_memo("[5] hello"), None);
+        assert_eq!(parse_order_memo("[17] foster:order:abc"), None);
+        // memos without the length prefix aren't what rpc nodes report
+        assert_eq!(parse_order_memo("foster:order:42"), None);
+    }
+
+    #[test]
+    fn parse_order_memo_several() {
+        assert_eq!(parse_order_memo("[5] hello; [17] foster:order:42"), Some(42));
+        // the first order memo wins
+        assert_eq!(parse_order_memo("[16] foster:order:7; [17] foster:order:42"), Some(7));
+    }
+}
@@ -1,31 +1,60 @@
 extern crate foster_data_layer;
 extern crate rocket;
 
-use chrono::Utc;
-use rocket::serde::json::{json, Json};
-use std::collections::HashMap;
+use chrono::{Duration, NaiveDateTime, Utc};
+use rocket::{
+    fairing::{AdHoc, Fairing, Info, Kind},
+    http::{Method, RawStr, Status},
+    response::{self, Responder},
+    serde::json::{json, Json},
+    Request, Response, Route,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use crate::{
-    editions::create_print,
+    editions::create_print_instructions,
     maddies::{get_ship_station_timestamp, ship_station_request},
 };
 use foster_data_layer::{
-    calculate_payment_shares, create_merch_order_and_order_products,
-    create_user_from_wallet_and_email, get_merch_order_info, get_merch_product_details,
-    get_single_nft_response, get_sol_to_usd_rate, get_user_by_wallet_id, mint_single_nft,
+    accept_offer, calculate_payment_shares, claim_merch_order_payment, close_listing,
+    complete_listing_sale, confirm_stock_reservation, consume_message_nonce, count_auction_bids,
+    create_auction_bid_once, create_merch_order_and_reserve_stock, create_offer_once,
+    create_user_from_wallet_and_email, expire_unpaid_merch_order, get_collection,
+    get_merch_order_info, get_merch_product_details, get_nfts_by_collection, get_offer,
+    get_offers_for_nft, get_single_nft_response, get_sol_to_usd_rate, get_stale_blink_orders,
+    get_user_by_id, get_user_by_wallet_id, mint_single_nft,
     models::{
-        ActionGetResponse, ActionParameter, ActionParameterOption, ActionPostLinks,
-        ActionPostRequest, ActionPostResponse, BlinkActionType, ErrorResponse, FulfillmentType,
-        LinkedAction, MerchItemBlinkData, MerchProductWithCurrentSupply, NewMerchOrder,
-        NewSingleNft, NextAction, NftActionBlinkData, PrintEditionRequest, ShipStationAddress,
-        ShipStationOrder, ShipStationOrderItem, ShipStationOrderItemOption, SingleNftResponse,
+        validate_action_parameters, ActionError, ActionGetResponse, ActionParameter,
+        ActionParameterOption, ActionParameterType, ActionPostLinks, ActionPostRequest,
+        ActionPostResponse, ActionPostResponseKind, ActionRuleObject, ActionVersion, ActionsJson,
+        BlinkActionType, BlockchainIds, ChainId, EmailVerificationState, ErrorResponse,
+        FulfillmentType, LinkedAction, LinkedActionType, MerchItemBlinkData, MerchProductVariant,
+        MerchProductWithCurrentSupply, NewAuctionBid, NewMerchOrder, NewOffer, NewSingleNft,
+        NewStockReservation, NextAction, NftActionBlinkData, ShipStationAddress, ShipStationOrder,
+        ShipStationOrderItem, ShipStationOrderItemOption, SignMessageData, SingleNftResponse,
         UpdateMerchOrder, Weight,
     },
-    update_order, MERCH_PAYMENT_ADDRESS,
+    release_expired_stock_reservations, settle_auction, update_order, update_user_email,
+    upsert_listing, MERCH_PAYMENT_ADDRESS,
 };
 use foster_solana::{
-    assert_minimum_balance, blinks::create_merch_blink_transaction, get_nft_from_das,
-    get_solana_network, lamports_to_sol, sol_to_lamports, validate_blink_payment,
+    assert_minimum_balance,
+    blinks::{
+        confirm_accept_offer_transaction, confirm_bid_transaction, confirm_buy_listing_transaction,
+        confirm_delist_transaction, confirm_list_transaction, confirm_offer_transaction,
+        confirm_print_transaction, confirm_settle_auction_transaction,
+        create_accept_offer_transaction, create_bid_transaction, create_buy_listing_transaction,
+        create_delist_transaction, create_list_transaction, create_merch_blink_transaction,
+        create_message_nonce, create_offer_transaction, create_payment_reference,
+        create_print_transaction, create_settle_auction_transaction, find_reference_signatures,
+        get_listing_lamports, get_recent_order_payments, open_message_state, seal_message_state,
+        verify_message_signature,
+    },
+    get_nft_from_das, get_solana_network, lamports_to_sol, sol_to_lamports, validate_blink_payment,
     validate_public_key, SOL_SYMBOL,
 };
 
@@ -35,24 +64,617 @@ macro_rules! uri {
     };
 }
 
-pub const MAINNET_BLOCKCHAIN_ID: &str = "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp";
-pub const DEVNET_BLOCKCHAIN_ID: &str = "solana:EtWTRABZaYq6iMfeYKouRu166VU2xqa1";
+pub fn get_chain_id() -> Result<ChainId, String> {
+    ChainId::from_network(&get_solana_network())
+}
+
+// `chain_id_fairing` makes sure the network is valid before any request is served
+pub fn get_blockchain_ids() -> BlockchainIds {
+    get_chain_id().unwrap_or_else(|e| panic!("{e}")).into()
+}
+
+// fails ignition on an unknown network instead of advertising the wrong chain
+pub fn chain_id_fairing() -> AdHoc {
+    AdHoc::try_on_ignite("blinks chain id", |rocket| async move {
+        match get_chain_id() {
+            Ok(chain_id) => {
+                log::info!("serving blinks for {chain_id}");
+                Ok(rocket)
+            }
+            Err(e) => {
+                log::error!("invalid blinks chain id: {e}");
+                Err(rocket)
+            }
+        }
+    })
+}
+
+pub const STOCK_RESERVATION_TTL_MINUTES: i64 = 15;
+pub const STOCK_RESERVATION_SWEEP_SECONDS: u64 = 60;
+
+// releases units held by orders whose checkout callback never arrived
+pub fn stock_reservation_fairing() -> AdHoc {
+    AdHoc::on_liftoff("blinks stock reservations", |_| {
+        Box::pin(async move {
+            rocket::tokio::spawn(async move {
+                let mut interval = rocket::tokio::time::interval(std::time::Duration::from_secs(
+                    STOCK_RESERVATION_SWEEP_SECONDS,
+                ));
+                loop {
+                    interval.tick().await;
+                    match release_expired_stock_reservations(Utc::now().naive_utc()) {
+                        Ok(0) => {}
+                        Ok(released) => {
+                            log::info!("released {released} expired stock reservations")
+                        }
+                        Err(e) => {
+                            log::error!("could not release expired stock reservations: {e}")
+                        }
+                    }
+                }
+            });
+        })
+    })
+}
+
+pub const BLINK_ORDER_EXPIRY_MINUTES: i64 = 60;
+pub const BLINK_ORDER_SWEEP_SECONDS: u64 = 300;
+// how many recent transactions of each payment address are checked for order memos
+pub const BLINK_ORDER_SIGNATURE_LIMIT: usize = 200;
+
+// counted since startup, and logged after every sweep
+pub static BLINK_ORDERS_PAID: AtomicU64 = AtomicU64::new(0);
+pub static BLINK_ORDERS_RECOVERED: AtomicU64 = AtomicU64::new(0);
+pub static BLINK_ORDERS_EXPIRED: AtomicU64 = AtomicU64::new(0);
+
+pub fn get_blink_order_abandonment_rate() -> f64 {
+    let paid =
+        BLINK_ORDERS_PAID.load(Ordering::Relaxed) + BLINK_ORDERS_RECOVERED.load(Ordering::Relaxed);
+    let expired = BLINK_ORDERS_EXPIRED.load(Ordering::Relaxed);
+    match paid + expired {
+        0 => 0.0,
+        total => expired as f64 / total as f64,
+    }
+}
+
+pub const BLINK_PAYMENT_RECONCILE_SECONDS: u64 = 60;
+// leaves recent orders to the checkout callback, which is quicker to ship them
+pub const BLINK_PAYMENT_GRACE_MINUTES: i64 = 2;
+
+// ships orders whose payment landed but whose checkout callback was dropped by the client, and
+// expires the orders the wallet never signed. a single task does both, so expiry always runs
+// right after the chain was checked and reconciliations never overlap
+pub fn blink_order_sweep_fairing() -> AdHoc {
+    AdHoc::on_liftoff("blinks order sweep", |_| {
+        Box::pin(async move {
+            rocket::tokio::spawn(async move {
+                let mut interval = rocket::tokio::time::interval(std::time::Duration::from_secs(
+                    BLINK_PAYMENT_RECONCILE_SECONDS,
+                ));
+                let expiry_ticks = BLINK_ORDER_SWEEP_SECONDS / BLINK_PAYMENT_RECONCILE_SECONDS;
+                for tick in 0u64.. {
+                    interval.tick().await;
+                    if let Err(e) = reconcile_blink_payments().await {
+                        log::error!("could not reconcile blink payments: {e}");
+                        continue;
+                    }
+                    if tick % expiry_ticks != 0 {
+                        continue;
+                    }
+                    if let Err(e) = expire_stale_blink_orders() {
+                        log::error!("could not expire stale blink orders: {e}");
+                    }
+                }
+            });
+        })
+    })
+}
+
+fn expire_stale_blink_orders() -> Result<(), String> {
+    let created_before = Utc::now().naive_utc() - Duration::minutes(BLINK_ORDER_EXPIRY_MINUTES);
+    // orders with a payment are never stale, even when they could not be shipped
+    let stale_orders = get_stale_blink_orders(created_before)?
+        .into_iter()
+        .filter(|order| order.transaction_id.is_none())
+        .collect::<Vec<_>>();
+    if stale_orders.is_empty() {
+        return Ok(());
+    }
+    for order in &stale_orders {
+        // a checkout may claim the order after it was read, then it is left alone
+        if expire_unpaid_merch_order(order.id)? {
+            BLINK_ORDERS_EXPIRED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    log::info!(
+        "blink orders: {} paid, {} recovered, {} expired, {:.1}% abandoned",
+        BLINK_ORDERS_PAID.load(Ordering::Relaxed),
+        BLINK_ORDERS_RECOVERED.load(Ordering::Relaxed),
+        BLINK_ORDERS_EXPIRED.load(Ordering::Relaxed),
+        get_blink_order_abandonment_rate() * 100.0
+    );
+    Ok(())
+}
+
+async fn reconcile_blink_payments() -> Result<(), String> {
+    let created_before = Utc::now().naive_utc() - Duration::minutes(BLINK_PAYMENT_GRACE_MINUTES);
+    let open_orders = get_stale_blink_orders(created_before)?
+        .into_iter()
+        .filter(|order| order.transaction_id.is_none())
+        .collect::<Vec<_>>();
+    if open_orders.is_empty() {
+        return Ok(());
+    }
+
+    // orders with a reference are looked up by it, scanning the payment history is only needed
+    // for older orders
+    let memo_orders = open_orders
+        .iter()
+        .filter(|order| order.solana_pay_reference.is_none())
+        .collect::<Vec<_>>();
+
+    // every payment goes to foster, but sellers are scanned too in case foster's history is busy
+    let mut addresses = match memo_orders.is_empty() {
+        true => vec![],
+        false => vec![MERCH_PAYMENT_ADDRESS.to_string()],
+    };
+    for order in &memo_orders {
+        let Some(payment_splits) = order.payment_splits.as_object() else {
+            continue;
+        };
+        for address in payment_splits.keys() {
+            if !addresses.contains(address) {
+                addresses.push(address.clone());
+            }
+        }
+    }
+
+    // older orders have no reference, but their payments are tagged with the order id in a memo.
+    // anyone can send that memo, so every tagged transaction is a candidate, oldest first
+    let mut payments: HashMap<i32, Vec<String>> = HashMap::new();
+    for address in &addresses {
+        for (order_id, signature) in get_recent_order_payments(address, BLINK_ORDER_SIGNATURE_LIMIT)
+            .await?
+            .into_iter()
+            .rev()
+        {
+            let signatures = payments.entry(order_id).or_default();
+            if !signatures.contains(&signature) {
+                signatures.push(signature);
+            }
+        }
+    }
+
+    for order in &open_orders {
+        let signatures = match &order.solana_pay_reference {
+            Some(reference) => find_reference_signatures(reference).await?,
+            None => payments.get(&order.id).cloned().unwrap_or_default(),
+        };
+        if signatures.is_empty() {
+            continue;
+        }
+        match fulfil_merch_order(order.id, &signatures).await {
+            Ok(_) => {
+                BLINK_ORDERS_RECOVERED.fetch_add(1, Ordering::Relaxed);
+                log::info!("order #{} was paid without a checkout", order.id);
+            }
+            // checked out while it was being reconciled
+            Err(BlinkError::AlreadyPaid { .. }) => {}
+            Err(e) => log::error!("could not reconcile order #{}: {}", order.id, e.log_message()),
+        }
+    }
+    Ok(())
+}
+
+pub enum BlinkError {
+    InvalidRequest(String),
+    InvalidPubkey(String),
+    InvalidParameters(ActionError),
+    InvalidPayment(String),
+    InsufficientFunds(String),
+    NotFound {
+        resource: String,
+        details: String,
+    },
+    SoldOut {
+        product: String,
+        supply: i32,
+    },
+    NotListed {
+        nft: String,
+    },
+    PriceChanged {
+        expected: f64,
+        actual: f64,
+    },
+    PriceMismatch {
+        query: f64,
+        data: f64,
+    },
+    QuantityMismatch {
+        query: u32,
+        data: u32,
+    },
+    NotOwner {
+        nft: String,
+    },
+    AuctionEnded {
+        nft: String,
+        ended_at: NaiveDateTime,
+    },
+    SaleNotStarted {
+        product: String,
+        starts_at: NaiveDateTime,
+    },
+    SaleEnded {
+        product: String,
+        ended_at: NaiveDateTime,
+    },
+    AlreadyPaid {
+        order_id: i32,
+        transaction_id: String,
+    },
+    AlreadyRecorded {
+        transaction_id: String,
+    },
+    UnknownAction(String),
+    NotImplemented(String),
+    Das(String),
+    Rpc(String),
+    ShipStation(String),
+    Internal(String),
+    // errors from shared handlers that already know how to respond
+    Response(ErrorResponse),
+}
+
+impl BlinkError {
+    pub fn not_found(resource: impl Into<String>) -> impl FnOnce(String) -> Self {
+        let resource = resource.into();
+        move |details| Self::NotFound { resource, details }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            Self::InvalidRequest(_)
+            | Self::InvalidPubkey(_)
+            | Self::InvalidPayment(_)
+            | Self::InsufficientFunds(_)
+            | Self::PriceMismatch { .. }
+            | Self::QuantityMismatch { .. } => Status::BadRequest,
+            Self::InvalidParameters(_) => Status::UnprocessableEntity,
+            Self::NotOwner { .. } => Status::Forbidden,
+            Self::NotFound { .. } | Self::UnknownAction(_) => Status::NotFound,
+            Self::SoldOut { .. }
+            | Self::NotListed { .. }
+            | Self::PriceChanged { .. }
+            | Self::AuctionEnded { .. }
+            | Self::SaleNotStarted { .. }
+            | Self::SaleEnded { .. }
+            | Self::AlreadyPaid { .. }
+            | Self::AlreadyRecorded { .. } => Status::Conflict,
+            Self::NotImplemented(_) => Status::NotImplemented,
+            Self::Das(_) | Self::Rpc(_) | Self::ShipStation(_) => Status::BadGateway,
+            Self::Internal(_) | Self::Response(_) => Status::InternalServerError,
+        }
+    }
+
+    // text shown to the wallet user, details of server faults are only logged
+    pub fn user_message(&self) -> String {
+        match self {
+            Self::InvalidRequest(message) => message.clone(),
+            Self::InvalidPubkey(_) => "invalid wallet address".to_string(),
+            Self::InvalidParameters(error) => error.message.clone(),
+            Self::InvalidPayment(_) => "payment could not be verified".to_string(),
+            Self::InsufficientFunds(_) => "insufficient balance for this purchase".to_string(),
+            Self::NotFound { resource, .. } => format!("could not find {resource}"),
+            Self::SoldOut { product, .. } => format!("{product} has sold out"),
+            Self::NotListed { nft } => format!("{nft} is no longer listed"),
+            Self::NotOwner { nft } => format!("only the owner of {nft} can do this"),
+            Self::PriceChanged { expected, actual } => format!(
+                "price changed from {SOL_SYMBOL}{expected:.2} to {SOL_SYMBOL}{actual:.2}, please refresh"
+            ),
+            Self::PriceMismatch { query, data } => format!(
+                "link price {SOL_SYMBOL}{query:.2} does not match submitted price {SOL_SYMBOL}{data:.2}"
+            ),
+            Self::QuantityMismatch { query, data } => {
+                format!("link quantity {query} does not match submitted quantity {data}")
+            }
+            Self::AuctionEnded { nft, ended_at } => {
+                format!("auction for {nft} ended at {ended_at} UTC")
+            }
+            Self::SaleNotStarted { product, starts_at } => {
+                format!("{product} sale starts at {starts_at} UTC")
+            }
+            Self::SaleEnded { product, ended_at } => {
+                format!("{product} sale ended at {ended_at} UTC")
+            }
+            Self::AlreadyPaid { order_id, .. } => format!("order #{order_id} is already paid"),
+            Self::AlreadyRecorded { .. } => "this transaction was already recorded".to_string(),
+            Self::UnknownAction(action) => format!("unknown blink action: {action}"),
+            Self::NotImplemented(action) => format!("{action} is not available yet"),
+            Self::Das(_) => "could not load nft details, please try again".to_string(),
+            Self::Rpc(_) => "could not reach the solana network, please try again".to_string(),
+            Self::ShipStation(_) => {
+                "could not submit your order for shipping, please contact support".to_string()
+            }
+            Self::Internal(_) | Self::Response(_) => {
+                "something went wrong, please try again".to_string()
+            }
+        }
+    }
+
+    // for errors that are only logged, never sent to the wallet
+    pub fn log_message(&self) -> String {
+        match self.details() {
+            Some(details) => format!("{}: {details}", self.user_message()),
+            None => self.user_message(),
+        }
+    }
+
+    fn details(&self) -> Option<&str> {
+        match self {
+            Self::InvalidPubkey(details)
+            | Self::InvalidPayment(details)
+            | Self::InsufficientFunds(details)
+            | Self::NotFound { details, .. }
+            | Self::Das(details)
+            | Self::Rpc(details)
+            | Self::ShipStation(details)
+            | Self::Internal(details) => Some(details),
+            Self::AlreadyPaid { transaction_id, .. }
+            | Self::AlreadyRecorded { transaction_id } => Some(transaction_id),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for BlinkError {
+    fn from(details: String) -> Self {
+        Self::Internal(details)
+    }
+}
+
+impl From<ActionError> for BlinkError {
+    fn from(error: ActionError) -> Self {
+        Self::InvalidParameters(error)
+    }
+}
+
+impl From<ErrorResponse> for BlinkError {
+    fn from(response: ErrorResponse) -> Self {
+        Self::Response(response)
+    }
+}
+
+impl From<BlinkError> for ActionError {
+    fn from(error: BlinkError) -> Self {
+        let status = error.status();
+        let message = error.user_message();
+        match error.details() {
+            Some(details) if status.code >= 500 => log::error!("{message}: {details}"),
+            Some(details) => log::info!("{message}: {details}"),
+            None => {}
+        }
+        match error {
+            BlinkError::InvalidParameters(error) => error,
+            _ => message.into(),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for BlinkError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        if let Self::Response(response) = self {
+            return response.respond_to(req);
+        }
+        let status = self.status();
+        Response::build_from(Json(ActionError::from(self)).respond_to(req)?)
+            .status(status)
+            .ok()
+    }
+}
+
+pub fn get_marketplace_url() -> &'static str {
+    match get_chain_id() {
+        Ok(ChainId::SolanaMainnet) => "https://fostermarketplace.app",
+        _ => "https://devnet.fostermarketplace.app",
+    }
+}
+
+// blink routes are mounted under `/blinks`, but exposed publicly under `/v1/blinks`
+pub const BLINKS_PUBLIC_PREFIX: &str = "/v1";
+pub const ACTIONS_JSON_RULES_ENV: &str = "BLINKS_ACTIONS_JSON_RULES";
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        blink_merch_item_get,
+        blink_merch_item_post,
+        blink_merch_item_verify_email_post,
+        blink_merch_item_email_verified_post,
+        blink_merch_item_checkout_post,
+        blink_merch_order_status_post,
+        blink_nft_get,
+        blink_nft_post,
+        blink_nft_buy_complete_post,
+        blink_nft_bid_complete_post,
+        blink_nft_offer_complete_post,
+        blink_nft_offers_get,
+        blink_nft_accept_offer_post,
+        blink_nft_offer_accepted_post,
+        blink_nft_manage_get,
+        blink_nft_manage_post,
+        blink_nft_manage_complete_post,
+        blink_nft_index_print_post,
+        blink_nft_print_status_post,
+        blink_collection_get,
+        blink_collection_open_post,
+        blink_preflight,
+    ]
+}
+
+pub const ACTIONS_CORS_HEADERS: [(&str, &str); 4] = [
+    ("Access-Control-Allow-Origin", "*"),
+    ("Access-Control-Allow-Methods", "GET,POST,PUT,OPTIONS"),
+    (
+        "Access-Control-Allow-Headers",
+        "Content-Type, Authorization, Content-Encoding, Accept-Encoding, X-Accept-Action-Version, X-Accept-Blockchain-Ids",
+    ),
+    ("Access-Control-Expose-Headers", "X-Action-Version, X-Blockchain-Ids"),
+];
+
+// adds the actions CORS headers to every blink response, including errors and preflights
+pub struct BlinksCors;
+
+#[rocket::async_trait]
+impl Fairing for BlinksCors {
+    fn info(&self) -> Info {
+        Info {
+            name: "blinks CORS headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let path = req.uri().path().as_str();
+        if !(path.starts_with("/blinks/") || path == "/actions.json") {
+            return;
+        }
+        for (name, value) in ACTIONS_CORS_HEADERS {
+            res.set_raw_header(name, value);
+        }
+    }
+}
+
+#[options("/<_path..>")]
+pub fn blink_preflight(_path: PathBuf) -> Status {
+    Status::Ok
+}
+
+pub fn get_actions_json() -> Result<ActionsJson, String> {
+    let mut rules = vec![
+        ActionRuleObject {
+            path_pattern: "/_/merch/*".to_string(),
+            api_path: format!("{BLINKS_PUBLIC_PREFIX}/blinks/_/merch/*"),
+        },
+        ActionRuleObject {
+            path_pattern: "/nft/*".to_string(),
+            api_path: format!("{BLINKS_PUBLIC_PREFIX}/blinks/nft/*"),
+        },
+        ActionRuleObject {
+            path_pattern: "/collection/*".to_string(),
+            api_path: format!("{BLINKS_PUBLIC_PREFIX}/blinks/collection/*"),
+        },
+        // idempotent rule, so blink urls are recognized as-is
+        ActionRuleObject {
+            path_pattern: format!("{BLINKS_PUBLIC_PREFIX}/blinks/**"),
+            api_path: format!("{BLINKS_PUBLIC_PREFIX}/blinks/**"),
+        },
+    ];
+
+    // rules are matched in order, so configured rules take precedence over the defaults
+    if let Ok(configured_rules) = std::env::var(ACTIONS_JSON_RULES_ENV) {
+        let configured_rules = serde_json::from_str::<Vec<ActionRuleObject>>(&configured_rules)
+            .map_err(|e| format!("could not parse {ACTIONS_JSON_RULES_ENV}: {e}"))?;
+        rules.splice(0..0, configured_rules);
+    }
+
+    Ok(ActionsJson { rules })
+}
+
+pub fn validate_actions_json<'a>(
+    actions_json: &ActionsJson,
+    routes: impl Iterator<Item = &'a Route> + Clone,
+) -> Result<(), String> {
+    let unresolved_rules = actions_json
+        .rules
+        .iter()
+        .filter(|rule| {
+            // absolute api paths point to other hosts and can't be checked here
+            !rule.api_path.starts_with("http")
+                && !routes
+                    .clone()
+                    .filter(|route| route.method == Method::Get)
+                    .any(|route| api_path_matches_route(&rule.api_path, route))
+        })
+        .map(|rule| format!("  {} -> {}", rule.path_pattern, rule.api_path))
+        .collect::<Vec<_>>();
+
+    if !unresolved_rules.is_empty() {
+        return Err(format!(
+            "actions.json rules without a matching blink route:\n{}",
+            unresolved_rules.join("\n")
+        ));
+    }
+
+    Ok(())
+}
+
+fn api_path_matches_route(api_path: &str, route: &Route) -> bool {
+    let api_path = api_path.split('?').next().unwrap_or_default();
+    let api_path = api_path.strip_prefix(BLINKS_PUBLIC_PREFIX).unwrap_or(api_path);
+    let mut api_segments = api_path.split('/').filter(|segment| !segment.is_empty());
+    let mut route_segments = route
+        .uri
+        .path()
+        .split('/')
+        .filter(|segment| !segment.is_empty());
 
-pub fn get_blockchain_id() -> String {
-    match get_solana_network().as_ref() {
-        "mainnet" => MAINNET_BLOCKCHAIN_ID.to_string(),
-        _ => DEVNET_BLOCKCHAIN_ID.to_string(),
+    loop {
+        match (api_segments.next(), route_segments.next()) {
+            (None, None) => return true,
+            (Some("**"), _) => return true,
+            (_, Some(segment)) if segment.starts_with('<') && segment.ends_with("..>") => {
+                return true
+            }
+            (Some(_), Some(segment)) if segment.starts_with('<') => continue,
+            (Some(api_segment), Some(segment)) if api_segment == segment => continue,
+            _ => return false,
+        }
     }
 }
 
+// fails ignition when a rule points to a route that is not mounted
+pub fn actions_json_fairing() -> AdHoc {
+    AdHoc::try_on_ignite("actions.json rules", |rocket| async move {
+        let result = get_actions_json()
+            .and_then(|actions_json| validate_actions_json(&actions_json, rocket.routes()));
+        match result {
+            Ok(()) => Ok(rocket),
+            Err(e) => {
+                log::error!("invalid actions.json: {e}");
+                Err(rocket)
+            }
+        }
+    })
+}
+
+// must be mounted at the root of the domain,
+// e.g. `.mount("/", routes![actions_json, actions_json_preflight])`
+#[get("/actions.json")]
+pub fn actions_json() -> Result<Json<ActionsJson>, BlinkError> {
+    Ok(Json(get_actions_json()?))
+}
+
+#[options("/actions.json")]
+pub fn actions_json_preflight() -> Status {
+    Status::Ok
+}
+
 #[get("/<_artist>/merch/<item_id>")]
-pub async fn blink_merch_item_get(_artist: &str, item_id: i32) -> ActionGetResponse {
-    let blockchain_id = get_blockchain_id();
-    let product = match get_merch_product_details(item_id) {
+pub async fn blink_merch_item_get(
+    _artist: &str,
+    item_id: i32,
+    version: ActionVersion,
+) -> ActionGetResponse {
+    let blockchain_ids = get_blockchain_ids();
+    let product = match get_merch_product_details(item_id)
+        .map_err(BlinkError::not_found(format!("product {item_id}")))
+    {
         Ok(product) => product,
         Err(e) => {
             return ActionGetResponse {
-                blockchain_id,
+                blockchain_ids,
                 title: "Invalid Product".to_string(),
                 // TODO: set url for invalid product
                 // icon: "".to_string(),
@@ -65,91 +687,53 @@ pub async fn blink_merch_item_get(_artist: &str, item_id: i32) -> ActionGetRespo
         }
     };
 
-    let mut parameters = vec![
-        ActionParameter {
-            parameter_type: "email".to_string(),
-            name: "email".to_string(),
-            label: "Email".to_string(),
-            required: true,
-            ..ActionParameter::default()
-        },
-        ActionParameter {
-            parameter_type: "textarea".to_string(),
-            name: "address".to_string(),
-            label: "Shipping Address".to_string(),
-            required: true,
-            ..ActionParameter::default()
-        },
-    ];
-
-    let fulfillment_type = product
-        .fulfillment_type
-        .parse::<FulfillmentType>()
-        .unwrap_or_else(|e| {
-            panic!(
-                "could not parse as FulfillmentType: {}: {e}",
-                product.fulfillment_type
-            )
-        });
-    // TODO: check if size is needed, and which sizes are supported
-    if matches!(fulfillment_type, FulfillmentType::Foster) {
-        parameters.splice(
-            0..0,
-            [ActionParameter {
-                parameter_type: "select".to_string(),
-                name: "size".to_string(),
-                label: "Size".to_string(),
-                required: true,
-                options: vec![
-                    ActionParameterOption {
-                        label: "Small".to_string(),
-                        value: "S".to_string(),
-                    },
-                    ActionParameterOption {
-                        label: "Medium".to_string(),
-                        value: "M".to_string(),
-                    },
-                    ActionParameterOption {
-                        label: "Large".to_string(),
-                        value: "L".to_string(),
-                    },
-                    ActionParameterOption {
-                        label: "Extra Large".to_string(),
-                        value: "XL".to_string(),
-                    },
-                    ActionParameterOption {
-                        label: "2XL".to_string(),
-                        value: "XXL".to_string(),
-                    },
-                    ActionParameterOption {
-                        label: "3XL".to_string(),
-                        value: "XXXL".to_string(),
-                    },
-                ],
-                ..ActionParameter::default()
-            }],
-        );
-    }
+    let parameters = match get_merch_item_variants(&product) {
+        Ok(variants) => get_merch_item_parameters(&variants),
+        Err(e) => {
+            return ActionGetResponse {
+                blockchain_ids,
+                icon: get_image_for_product(&product).unwrap_or_default(),
+                title: product.name,
+                description: product.description,
+                label: "Buy".to_string(),
+                disabled: true,
+                error: Some(BlinkError::Internal(e).into()),
+                ..ActionGetResponse::default()
+            };
+        }
+    };
+    let query = parameters
+        .iter()
+        .map(|parameter| format!("{0}={{{0}}}", parameter.name))
+        .collect::<Vec<_>>()
+        .join("&");
+    // the email is verified by signing a message before the order transaction is built,
+    // clients without message actions order right away and the email is only kept on the order
+    let (action_type, href) = match version.supports(ActionVersion::LINKED_ACTION_TYPES) {
+        true => (
+            LinkedActionType::Message,
+            format!("/v1/blinks/{_artist}/merch/{item_id}/verify-email?{query}"),
+        ),
+        false => (
+            LinkedActionType::Transaction,
+            format!("/v1/blinks/{_artist}/merch/{item_id}/?{query}"),
+        ),
+    };
 
-    // + 2% slippage
-    let usd_per_sol = get_sol_to_usd_rate().await.unwrap_or_default() / 1.02;
-    // fixed $15 for shipping
-    let usd_amount = (product.selling_price + 1500) as f64 / 100.0;
-    let sol_amount = usd_amount / usd_per_sol;
+    let (sol_amount, usd_amount) = get_merch_item_price(&product, None).await;
 
     // TODO: maybe add selector to choose payment token between sol or usdc
     ActionGetResponse {
-        blockchain_id,
+        blockchain_ids,
         // TODO: add url for products with missing image
         icon: get_image_for_product(&product).unwrap_or_default(),
         title: product.name,
         description: product.description,
         label: "Buy".to_string(),
         links: vec![LinkedAction {
+            action_type: Some(action_type),
             label: format!("Buy for {SOL_SYMBOL}{sol_amount:.2} | ${usd_amount:.2}"),
-            href: format!(
-                "/v1/blinks/{_artist}/merch/{item_id}/?size={{size}}&email={{email}}&address={{address}}",
-            ),
+            href,
             parameters,
         }]
         .into(),
@@ -157,7 +741,93 @@ pub async fn blink_merch_item_get(_artist: &str, item_id: i32) -> ActionGetRespo
     }
 }
 
-fn get_image_for_product(product: &MerchProductWithCurrentSupply) -> Option<String> {
+async fn get_merch_item_price(
+    product: &MerchProductWithCurrentSupply,
+    variant: Option<&MerchProductVariant>,
+) -> (f64, f64) {
+    // + 2% slippage
+    let usd_per_sol = get_sol_to_usd_rate().await.unwrap_or_default() / 1.02;
+    let price_delta = variant.map(|variant| variant.price_delta).unwrap_or_default();
+    // fixed $15 for shipping
+    let usd_amount = (product.selling_price + price_delta + 1500) as f64 / 100.0;
+    let sol_amount = usd_amount / usd_per_sol;
+    (sol_amount, usd_amount)
+}
+
+// checks the submitted options, and returns the variant they select if the product has any
+fn validate_merch_item_options(
+    product: &MerchProductWithCurrentSupply,
+    options: &MerchItemBlinkData<'_>,
+) -> Result<Option<MerchProductVariant>, BlinkError> {
+    let variants = get_merch_item_variants(product)?;
+
+    let values = MerchProductVariant::ATTRIBUTES
+        .iter()
+        .map(|name| (*name, options.variant_attribute(name).map(|value| value.to_string())))
+        .chain([
+            ("email", Some(options.email.to_string())),
+            ("address", Some(options.address.to_string())),
+        ])
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect::<HashMap<_, _>>();
+    validate_action_parameters(&get_merch_item_parameters(&variants), &values)?;
+
+    if variants.is_empty() {
+        return Ok(None);
+    }
+    let variant = variants
+        .into_iter()
+        .find(|variant| {
+            MerchProductVariant::ATTRIBUTES
+                .iter()
+                .all(|name| variant.attribute(name) == options.variant_attribute(name))
+        })
+        .ok_or_else(|| {
+            BlinkError::InvalidRequest(format!(
+                "{} is not available in this combination",
+                product.name
+            ))
+        })?;
+    if variant.stock == Some(0) {
+        return Err(BlinkError::SoldOut {
+            product: format!("{} ({})", product.name, variant.label()),
+            supply: 0,
+        });
+    }
+
+    Ok(Some(variant))
+}
+
+// foster fulfilled products without configured variants are offered in these sizes
+const LEGACY_MERCH_SIZES: [(&str, &str); 6] = [
+    ("Small", "S"),
+    ("Medium", "M"),
+    ("Large", "L"),
+    ("Extra Large", "XL"),
+    ("2XL", "XXL"),
+    ("3XL", "XXXL"),
+];
+
+fn get_merch_item_variants(
+    product: &MerchProductWithCurrentSupply,
+) -> Result<Vec<MerchProductVariant>, String> {
+    if let Some(variants) = product.options.get("variants") {
+        let variants = serde_json::from_value::<Vec<MerchProductVariant>>(variants.clone())
+            .map_err(|e| format!("could not parse variants of product {}: {e}", product.id))?;
+        // stock is reserved by sku, labels can change while orders are open
+        if let Some(variant) = variants
+            .iter()
+            .find(|variant| variant.stock.is_some() && variant.sku.is_none())
+        {
+            return Err(format!(
+                "variant {} of product {} has stock but no sku",
+                variant.label(),
+                product.id
+            ));
+        }
+        return Ok(variants);
+    }
+
     let fulfillment_type = product
         .fulfillment_type
         .parse::<FulfillmentType>()
@@ -167,72 +837,171 @@ fn get_image_for_product(product: &MerchProductWithCurrentSupply) -> Option<Stri
                 product.fulfillment_type
             )
         });
-    match fulfillment_type {
-        FulfillmentType::Foster => product
-            .options
-            .get("addons")?
-            .get(0)?
-            .get("mockup_url")?
-            .as_str()
-            .map(|s| s.to_string()),
-        FulfillmentType::User => product
-            .options
-            .get("product_images")?
-            .get(0)?
-            .as_str()
-            .map(|s| s.to_string()),
-    }
+    Ok(match fulfillment_type {
+        FulfillmentType::Foster => LEGACY_MERCH_SIZES
+            .iter()
+            .map(|(_, size)| MerchProductVariant {
+                size: Some(size.to_string()),
+                ..MerchProductVariant::default()
+            })
+            .collect(),
+        FulfillmentType::User => vec![],
+    })
 }
 
-#[post(
-    "/<_artist>/merch/<item_id>?<options..>",
-    format = "application/json",
-    data = "<request>",
-    rank = 2
-)]
-pub async fn blink_merch_item_post(
-    _artist: &str,
-    item_id: i32,
-    options: MerchItemBlinkData<'_>,
+fn get_merch_item_parameters(variants: &[MerchProductVariant]) -> Vec<ActionParameter> {
+    // one select per attribute the variants use, without values that are sold out everywhere
+    let mut parameters = vec![];
+    for (name, label) in [("size", "Size"), ("color", "Color"), ("material", "Material")] {
+        let mut options: Vec<ActionParameterOption> = vec![];
+        for variant in variants.iter().filter(|variant| variant.stock != Some(0)) {
+            let Some(value) = variant.attribute(name) else {
+                continue;
+            };
+            if options.iter().any(|option| option.value == value) {
+                continue;
+            }
+            let value_label = LEGACY_MERCH_SIZES
+                .iter()
+                .find(|(_, size)| name == "size" && *size == value)
+                .map_or(value, |(size_label, _)| *size_label);
+            let price_delta = variants
+                .iter()
+                .filter(|variant| variant.attribute(name) == Some(value))
+                .map(|variant| variant.price_delta)
+                .min()
+                .unwrap_or_default();
+            options.push(ActionParameterOption {
+                label: match price_delta {
+                    1.. => format!("{value_label} (+${:.2})", price_delta as f64 / 100.0),
+                    _ => value_label.to_string(),
+                },
+                value: value.to_string(),
+                selected: false,
+            });
+        }
+        if !options.is_empty() {
+            parameters.push(ActionParameter {
+                parameter_type: Some(ActionParameterType::Select),
+                name: name.to_string(),
+                label: label.to_string(),
+                required: true,
+                options,
+                ..ActionParameter::default()
+            });
+        }
+    }
+
+    parameters.extend([
+        ActionParameter {
+            parameter_type: Some(ActionParameterType::Email),
+            name: "email".to_string(),
+            label: "Email".to_string(),
+            required: true,
+            ..ActionParameter::default()
+        },
+        ActionParameter {
+            parameter_type: Some(ActionParameterType::Textarea),
+            name: "address".to_string(),
+            label: "Shipping Address".to_string(),
+            required: true,
+            ..ActionParameter::default()
+        },
+    ]);
+
+    parameters
+}
+
+fn get_image_for_product(product: &MerchProductWithCurrentSupply) -> Option<String> {
+    let fulfillment_type = product
+        .fulfillment_type
+        .parse::<FulfillmentType>()
+        .unwrap_or_else(|e| {
+            panic!(
+                "could not parse as FulfillmentType: {}: {e}",
+                product.fulfillment_type
+            )
+        });
+    match fulfillment_type {
+        FulfillmentType::Foster => product
+            .options
+            .get("addons")?
+            .get(0)?
+            .get("mockup_url")?
+            .as_str()
+            .map(|s| s.to_string()),
+        FulfillmentType::User => product
+            .options
+            .get("product_images")?
+            .get(0)?
+            .as_str()
+            .map(|s| s.to_string()),
+    }
+}
+
+#[post(
+    "/<_artist>/merch/<item_id>?<options..>",
+    format = "application/json",
+    data = "<request>",
+    rank = 2
+)]
+pub async fn blink_merch_item_post(
+    _artist: &str,
+    item_id: i32,
+    options: MerchItemBlinkData<'_>,
+    version: ActionVersion,
     request: Json<ActionPostRequest<'_>>,
-) -> Result<ActionPostResponse, ErrorResponse> {
+) -> Result<ActionPostResponse, BlinkError> {
     let MerchItemBlinkData {
         size,
+        color,
+        material,
         email,
         address,
     } = &options;
 
-    let product = get_merch_product_details(item_id)?;
+    let product = get_merch_product_details(item_id)
+        .map_err(BlinkError::not_found(format!("product {item_id}")))?;
+    let variant = validate_merch_item_options(&product, &options)?;
+
     if let Some(supply) = product.supply {
         if (product.current_supply as i32) >= supply {
-            return Err(format!(
-                "product {} has sold out; max supply: {supply}",
-                product.name
-            )
-            .into());
+            return Err(BlinkError::SoldOut {
+                product: product.name,
+                supply,
+            });
         }
     }
     let now = Utc::now().naive_utc();
     if let Some(sale_start_at) = product.sale_start_at {
         if now < sale_start_at {
-            return Err(format!("product {} sale starts at {sale_start_at}", product.name).into());
+            return Err(BlinkError::SaleNotStarted {
+                product: product.name,
+                starts_at: sale_start_at,
+            });
         }
     }
     if let Some(sale_end_at) = product.sale_end_at {
         if now > sale_end_at {
-            return Err(format!("product {} sale ended at {sale_end_at}", product.name).into());
+            return Err(BlinkError::SaleEnded {
+                product: product.name,
+                ended_at: sale_end_at,
+            });
         }
     }
 
-    let seller_amount = product.selling_price - product.foster_amount;
+    let price_delta = variant.as_ref().map(|variant| variant.price_delta).unwrap_or_default();
+    let seller_amount = product.selling_price + price_delta - product.foster_amount;
     // fixed $15 for shipping
     let foster_amount = product.foster_amount + 1500;
     let usd_amount = seller_amount + foster_amount;
 
-    let user_pubkey = validate_public_key(request.account)?;
+    let user_pubkey = validate_public_key(request.account).map_err(BlinkError::InvalidPubkey)?;
+    // emails are only linked to wallets by `blink_merch_item_email_verified_post`,
+    // otherwise the email is just the contact of this order
     let user = match get_user_by_wallet_id(user_pubkey) {
         Some(user) => user,
-        None => create_user_from_wallet_and_email(user_pubkey, Some(email)),
+        None => create_user_from_wallet_and_email(user_pubkey, None),
     };
 
     let seller_shares = calculate_payment_shares(
@@ -253,14 +1022,21 @@ pub async fn blink_merch_item_post(
         .collect::<HashMap<_, _>>();
 
     let total_lamports = seller_shares_lamports.values().sum::<u64>();
-    assert_minimum_balance(request.account, total_lamports + 20_000).await?;
+    assert_minimum_balance(request.account, total_lamports + 20_000)
+        .await
+        .map_err(BlinkError::InsufficientFunds)?;
 
-    let (order, _) = create_merch_order_and_order_products(
+    let payment_reference = create_payment_reference();
+    let variant_label = variant.as_ref().map(|variant| variant.label());
+    // the order and the reservation of its unit are created in one db transaction, so an order
+    // is never left without stock held for it and concurrent buyers can't oversell a drop
+    let reserved_order = create_merch_order_and_reserve_stock(
         NewMerchOrder {
             user_id: user.id,
             status: "created-blink",
             shipping_address: Some(&json!({
-                "rawAddress": address
+                "rawAddress": address,
+                "email": email,
             })),
             // TODO: remove fulfillment type
             fulfillment_type: "",
@@ -271,31 +1047,137 @@ pub async fn blink_merch_item_post(
                 .map_err(|e| format!("could not serialize payment splits: {e}"))?,
             payment_method: "SOL",
             transaction_id: None,
+            solana_pay_reference: Some(&payment_reference),
+        },
+        // the selected variant is needed again to ship the order
+        vec![(
+            product.id,
+            1,
+            Some(json!({
+                "size": size,
+                "color": color,
+                "material": material,
+            })),
+        )],
+        NewStockReservation {
+            product_id: product.id,
+            // variants without stock only count against the supply of the product
+            variant_sku: variant
+                .as_ref()
+                .filter(|variant| variant.stock.is_some())
+                .and_then(|variant| variant.sku.as_deref()),
+            quantity: 1,
+            expires_at: Utc::now().naive_utc() + Duration::minutes(STOCK_RESERVATION_TTL_MINUTES),
         },
-        vec![(product.id, 1, None)],
     )?;
+    let Some((order, _)) = reserved_order else {
+        return Err(BlinkError::SoldOut {
+            product: product.name,
+            supply: product.supply.unwrap_or_default(),
+        });
+    };
 
-    let transaction = create_merch_blink_transaction(user_pubkey, seller_shares_lamports).await?;
+    let transaction = create_merch_blink_transaction(
+        user_pubkey,
+        order.id,
+        &payment_reference,
+        seller_shares_lamports,
+    )
+    .await?;
 
     Ok(ActionPostResponse {
-        blockchain_id: get_blockchain_id(),
-        transaction,
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Transaction { transaction },
         message: Some(
             [
                 Some(format!("Placing Order #{}: {}", order.id, product.name)),
-                size.map(|size| size.to_string()),
+                variant_label,
             ]
             .into_iter()
             .flatten()
             .collect::<Vec<_>>()
             .join(" | "),
         ),
+        // the pending card checks on the order by its reference, only clients that can't follow
+        // its post link need the checkout callback
+        links: Some(ActionPostLinks {
+            next: match version.supports(ActionVersion::LINKED_ACTION_TYPES) {
+                true => NextAction::inline(merch_order_pending_card(
+                    order.id,
+                    get_image_for_product(&product).unwrap_or_default(),
+                )),
+                false => NextAction::Post {
+                    href: uri!(blink_merch_item_checkout_post(order_id = order.id)).to_string(),
+                },
+            },
+        }),
+    })
+}
+
+pub const EMAIL_VERIFICATION_TTL_MINUTES: i64 = 10;
+
+#[post(
+    "/<artist>/merch/<item_id>/verify-email?<options..>",
+    format = "application/json",
+    data = "<request>"
+)]
+pub async fn blink_merch_item_verify_email_post(
+    artist: &str,
+    item_id: i32,
+    options: MerchItemBlinkData<'_>,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionPostResponse, BlinkError> {
+    let MerchItemBlinkData {
+        size,
+        color,
+        material,
+        email,
+        address,
+    } = &options;
+
+    let product = get_merch_product_details(item_id)
+        .map_err(BlinkError::not_found(format!("product {item_id}")))?;
+    validate_merch_item_options(&product, &options)?;
+    let user_pubkey = validate_public_key(request.account).map_err(BlinkError::InvalidPubkey)?;
+
+    let issued_at = Utc::now();
+    let expires_at = issued_at + Duration::minutes(EMAIL_VERIFICATION_TTL_MINUTES);
+    let message = SignMessageData {
+        version: "1".to_string(),
+        domain: get_marketplace_url().trim_start_matches("https://").to_string(),
+        address: user_pubkey.to_string(),
+        nonce: create_message_nonce(),
+        issued_at: issued_at.to_rfc3339(),
+        chain_id: get_chain_id().ok().map(|chain_id| chain_id.to_string()),
+        statement: Some(format!(
+            "Link {email} to your Foster account to order {}. This request expires at {}.",
+            product.name,
+            expires_at.to_rfc3339()
+        )),
+    };
+    let state = serde_json::to_vec(&EmailVerificationState {
+        message: message.clone(),
+        email: email.to_string(),
+        expires_at: expires_at.naive_utc(),
+    })
+    .map_err(|e| format!("could not serialize email verification: {e}"))?;
+
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Message {
+            data: message,
+            state: Some(seal_message_state(&state)?),
+        },
+        message: Some(format!("Sign to verify {email}")),
         links: Some(ActionPostLinks {
             next: NextAction::Post {
-                href: uri!(blink_merch_item_checkout_post(
-                    order_id = order.id,
-                    email = email.as_ref(),
-                    size = size.as_deref()
+                href: uri!(blink_merch_item_email_verified_post(
+                    artist = artist,
+                    item_id = item_id,
+                    size = size.as_deref(),
+                    color = color.as_deref(),
+                    material = material.as_deref(),
+                    address = address.as_ref()
                 ))
                 .to_string(),
             },
@@ -303,31 +1185,297 @@ pub async fn blink_merch_item_post(
     })
 }
 
-#[post("/merch/<order_id>/checkout?<email>&<size>", data = "<request>")]
-pub async fn blink_merch_item_checkout_post(
-    order_id: i32,
-    email: &str,
+#[post(
+    "/<artist>/merch/<item_id>/email-verified?<size>&<color>&<material>&<address>",
+    format = "application/json",
+    data = "<request>"
+)]
+pub async fn blink_merch_item_email_verified_post(
+    artist: &str,
+    item_id: i32,
     size: Option<&str>,
+    color: Option<&str>,
+    material: Option<&str>,
+    address: &str,
     request: Json<ActionPostRequest<'_>>,
-) -> Result<ActionGetResponse, ErrorResponse> {
-    let payment_reference = request
+) -> Result<ActionGetResponse, BlinkError> {
+    let signature = request
         .signature
-        .as_ref()
-        .ok_or_else(|| "invalid request: missing signature".to_string())?;
+        .ok_or_else(|| BlinkError::InvalidRequest("missing signature".to_string()))?;
+    let state = request
+        .state
+        .ok_or_else(|| BlinkError::InvalidRequest("missing state".to_string()))?;
+    let state = open_message_state(state)
+        .and_then(|state| {
+            serde_json::from_slice::<EmailVerificationState>(&state)
+                .map_err(|e| format!("could not parse email verification: {e}"))
+        })
+        .map_err(|e| {
+            log::info!("invalid email verification state: {e}");
+            BlinkError::InvalidRequest("invalid email verification".to_string())
+        })?;
+
+    if state.message.address != request.account {
+        return Err(BlinkError::InvalidRequest(
+            "email verification was issued for another wallet".to_string(),
+        ));
+    }
+    if Utc::now().naive_utc() > state.expires_at {
+        return Err(BlinkError::InvalidRequest(
+            "email verification expired, please try again".to_string(),
+        ));
+    }
+    verify_message_signature(request.account, &state.message.to_message_text(), signature)
+        .map_err(|e| {
+            log::info!("invalid email verification signature: {e}");
+            BlinkError::InvalidRequest("invalid message signature".to_string())
+        })?;
+    // the sealed state can be replayed until it expires, so each nonce is only accepted once
+    if !consume_message_nonce(&state.message.nonce, state.expires_at)? {
+        return Err(BlinkError::InvalidRequest(
+            "email verification was already used, please try again".to_string(),
+        ));
+    }
+
+    match get_user_by_wallet_id(request.account) {
+        Some(user) => update_user_email(user.id, &state.email)?,
+        None => {
+            create_user_from_wallet_and_email(request.account, Some(&state.email));
+        }
+    };
+
+    let product = get_merch_product_details(item_id)
+        .map_err(BlinkError::not_found(format!("product {item_id}")))?;
+    let options = MerchItemBlinkData {
+        size,
+        color,
+        material,
+        email: state.email.as_str().into(),
+        address: address.into(),
+    };
+    let variant = validate_merch_item_options(&product, &options)?;
+    let (sol_amount, usd_amount) = get_merch_item_price(&product, variant.as_ref()).await;
+    let href = format!(
+        "/v1/blinks/{artist}/merch/{item_id}/?{}",
+        [
+            ("size", size),
+            ("color", color),
+            ("material", material),
+            ("email", Some(state.email.as_str())),
+            ("address", Some(address)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| {
+            Some(format!("{name}={}", RawStr::new(value?).percent_encode()))
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+    );
+
+    Ok(ActionGetResponse {
+        blockchain_ids: get_blockchain_ids(),
+        icon: get_image_for_product(&product).unwrap_or_default(),
+        title: product.name,
+        description: format!("{} is verified, confirm your order below.", state.email),
+        label: "Buy".to_string(),
+        links: vec![LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: format!("Buy for {SOL_SYMBOL}{sol_amount:.2} | ${usd_amount:.2}"),
+            href,
+            parameters: vec![],
+        }]
+        .into(),
+        ..ActionGetResponse::default()
+    })
+}
+
+#[post("/merch/<order_id>/checkout", data = "<request>")]
+pub async fn blink_merch_item_checkout_post(
+    order_id: i32,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionGetResponse, BlinkError> {
+    let order = get_merch_order_info(order_id)
+        .map_err(BlinkError::not_found(format!("order #{order_id}")))?;
+    // the payment is looked up by the order's reference. the client's signature is tried too, in
+    // case the rpc node hasn't indexed the reference yet, and is checked against the order as well
+    let mut signatures = match &order.solana_pay_reference {
+        Some(reference) => find_reference_signatures(reference)
+            .await
+            .map_err(BlinkError::Rpc)?,
+        None => vec![],
+    };
+    if let Some(signature) = request.signature {
+        if !signatures.iter().any(|known| known == signature) {
+            signatures.push(signature.to_string());
+        }
+    }
+    // orders created before payment references can only be found by the client's signature
+    if signatures.is_empty() && order.solana_pay_reference.is_none() {
+        return Err(BlinkError::InvalidRequest("missing signature".to_string()));
+    }
+
+    let response = fulfil_merch_order(order_id, &signatures).await?;
+    BLINK_ORDERS_PAID.fetch_add(1, Ordering::Relaxed);
+    Ok(response)
+}
+
+// polled from the pending card, ships the order as soon as its payment is found
+#[post("/merch/<order_id>/status")]
+pub async fn blink_merch_order_status_post(
+    order_id: i32,
+) -> Result<ActionPostResponse, BlinkError> {
+    let order = get_merch_order_info(order_id)
+        .map_err(BlinkError::not_found(format!("order #{order_id}")))?;
+    let product = get_merch_product_details(order.items[0].id)
+        .map_err(BlinkError::not_found(format!("product {}", order.items[0].id)))?;
+    let icon = get_image_for_product(&product).unwrap_or_default();
+
+    let card = match (order.transaction_id, order.solana_pay_reference) {
+        (Some(_), _) => merch_order_completed_card(order_id, icon),
+        (None, Some(reference)) => {
+            let signatures = find_reference_signatures(&reference)
+                .await
+                .map_err(BlinkError::Rpc)?;
+            if signatures.is_empty() {
+                merch_order_pending_card(order_id, icon)
+            } else {
+                match fulfil_merch_order(order_id, &signatures).await {
+                    Ok(response) => {
+                        BLINK_ORDERS_PAID.fetch_add(1, Ordering::Relaxed);
+                        response
+                    }
+                    // the reconciler got to it first
+                    Err(BlinkError::AlreadyPaid { .. }) => {
+                        merch_order_completed_card(order_id, icon)
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        (None, None) => {
+            return Err(BlinkError::InvalidRequest(format!(
+                "order #{order_id} has no payment reference"
+            )));
+        }
+    };
+
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Post,
+        message: None,
+        links: Some(ActionPostLinks {
+            next: NextAction::inline(card),
+        }),
+    })
+}
+
+fn merch_order_pending_card(order_id: i32, icon: String) -> ActionGetResponse {
+    ActionGetResponse {
+        blockchain_ids: get_blockchain_ids(),
+        title: format!("Order #{order_id}"),
+        icon,
+        description: "Waiting for your payment to confirm, this can take a few seconds."
+            .to_string(),
+        label: "Payment pending".to_string(),
+        links: vec![LinkedAction {
+            action_type: Some(LinkedActionType::Post),
+            label: "Check order status".to_string(),
+            href: uri!(blink_merch_order_status_post(order_id = order_id)).to_string(),
+            parameters: vec![],
+        }]
+        .into(),
+        ..ActionGetResponse::default()
+    }
+}
+
+fn merch_order_completed_card(order_id: i32, icon: String) -> ActionGetResponse {
+    ActionGetResponse {
+        blockchain_ids: get_blockchain_ids(),
+        action_type: BlinkActionType::Completed,
+        title: format!("Order #{order_id}"),
+        // TODO: show confetti GIF
+        icon,
+        description: format!("Manage your order at {}/orders/{order_id}", get_marketplace_url()),
+        label: "Order placed successfully!".to_string(),
+        disabled: true,
+        ..ActionGetResponse::default()
+    }
+}
 
+// shared by the checkout callback and the payment reconciler, so paid orders ship either way
+async fn fulfil_merch_order(
+    order_id: i32,
+    signatures: &[String],
+) -> Result<ActionGetResponse, BlinkError> {
     let order = get_merch_order_info(order_id)
-        .map_err(|e| format!("could not find order with id {order_id}: {e}"))?;
-    if let Some(tx) = order.transaction_id {
-        return Err(format!("order {order_id} already paid by tx {tx}").into());
+        .map_err(BlinkError::not_found(format!("order #{order_id}")))?;
+    if let Some(transaction_id) = order.transaction_id {
+        return Err(BlinkError::AlreadyPaid {
+            order_id,
+            transaction_id,
+        });
     }
 
-    validate_blink_payment(payment_reference, order).await?;
+    // the first transaction that actually pays this order is its payment
+    let mut payment_reference = None;
+    let mut errors = vec![];
+    for signature in signatures {
+        match validate_blink_payment(signature, order).await {
+            Ok(_) => {
+                payment_reference = Some(signature.as_str());
+                break;
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    let payment_reference = payment_reference.ok_or_else(|| {
+        BlinkError::InvalidPayment(match errors.is_empty() {
+            true => format!("no payment found for order #{order_id}"),
+            false => errors.join(", "),
+        })
+    })?;
+    // the checkout callback and the reconciler can both get here for the same order, only the
+    // one that sets the paid status ships it. a paid order stays paid if shipping fails below
+    if !claim_merch_order_payment(order_id, payment_reference)? {
+        return Err(BlinkError::AlreadyPaid {
+            order_id,
+            transaction_id: payment_reference.to_string(),
+        });
+    }
 
-    let product = get_merch_product_details(order.items[0].id)?;
+    let product = get_merch_product_details(order.items[0].id)
+        .map_err(BlinkError::not_found(format!("product {}", order.items[0].id)))?;
+    // a reservation that expired before the payment landed is taken again if stock allows
+    if !confirm_stock_reservation(order_id)? {
+        log::error!("order #{order_id} was paid with {payment_reference} after its stock ran out");
+        return Err(BlinkError::SoldOut {
+            product: product.name,
+            supply: product.supply.unwrap_or_default(),
+        });
+    }
     let product_image = get_image_for_product(&product).unwrap_or_default();
+    // the variant was checked when the order was placed, so only look it up for shipping
+    let selected = order.items[0]
+        .options
+        .clone()
+        .and_then(|options| serde_json::from_value::<MerchProductVariant>(options).ok())
+        .unwrap_or_default();
+    let variant = get_merch_item_variants(&product)?.into_iter().find(|variant| {
+        MerchProductVariant::ATTRIBUTES
+            .iter()
+            .all(|name| variant.attribute(name) == selected.attribute(name))
+    });
 
-    let user = get_user_by_wallet_id(request.account)
-        .ok_or_else(|| format!("could not find user with account {}", request.account))?;
+    let user = get_user_by_id(order.user_id).ok_or_else(|| BlinkError::NotFound {
+        resource: "user".to_string(),
+        details: format!("no user with id {}", order.user_id),
+    })?;
+
+    let customer_email = order
+        .shipping_address
+        .as_ref()
+        .and_then(|address| Some(address.get("email")?.as_str()?.to_string()))
+        .or(user.email);
 
     // TODO: parse address
     let address = ShipStationAddress {
@@ -355,7 +1503,7 @@ pub async fn blink_merch_item_checkout_post(
             payment_date: Some(order_date),
             order_status: "awaiting_shipment".to_string(),
             customer_id: None,
-            customer_email: user.email.unwrap_or_else(|| email.to_string()),
+            customer_email: customer_email.unwrap_or_default(),
 
             bill_to: Some(address.clone()),
             ship_to: Some(address),
@@ -363,7 +1511,7 @@ pub async fn blink_merch_item_checkout_post(
             items: vec![ShipStationOrderItem {
                 order_item_id: 0,
                 line_item_key: product.id.to_string(),
-                sku: None,
+                sku: variant.as_ref().and_then(|variant| variant.sku.clone()),
                 name: product.name.clone(),
                 image_url: Some(product_image.clone()),
                 weight: Weight {
@@ -371,7 +1519,10 @@ pub async fn blink_merch_item_checkout_post(
                     units: "ounces".to_string(),
                 },
                 quantity: 1,
-                unit_price: product.selling_price as f64 / 100.0,
+                unit_price: (product.selling_price
+                    + variant.as_ref().map(|variant| variant.price_delta).unwrap_or_default())
+                    as f64
+                    / 100.0,
                 tax_amount: None,
                 options: [
                     Some(ShipStationOrderItemOption {
@@ -380,14 +1531,7 @@ pub async fn blink_merch_item_checkout_post(
                     }),
                     Some(ShipStationOrderItemOption {
                         name: "fosterUrl".to_string(),
-                        value: format!(
-                            "{}/_/merch/{}",
-                            match network.as_str() {
-                                "mainnet" => "https://fostermarketplace.app",
-                                _ => "https://devnet.fostermarketplace.app",
-                            },
-                            product.id
-                        ),
+                        value: format!("{}/_/merch/{}", get_marketplace_url(), product.id),
                     }),
                     Some(ShipStationOrderItemOption {
                         name: "assetUrl".to_string(),
@@ -421,9 +1565,17 @@ pub async fn blink_merch_item_checkout_post(
                             name: "technique".to_string(),
                             value: technique.to_string(),
                         }),
-                    size.map(|size| ShipStationOrderItemOption {
+                    selected.size.map(|size| ShipStationOrderItemOption {
                         name: "size".to_string(),
-                        value: size.to_string(),
+                        value: size,
+                    }),
+                    selected.color.map(|color| ShipStationOrderItemOption {
+                        name: "color".to_string(),
+                        value: color,
+                    }),
+                    selected.material.map(|material| ShipStationOrderItemOption {
+                        name: "material".to_string(),
+                        value: material,
                     }),
                 ]
                 .into_iter()
@@ -444,67 +1596,1254 @@ pub async fn blink_merch_item_checkout_post(
             gift: false,
             gift_message: None,
 
-            payment_method: Some(format!("blinks: tx {payment_reference}")),
-            requested_shipping_service: Some("blinks".to_string()),
+            payment_method: Some(format!("blinks: tx {payment_reference}")),
+            requested_shipping_service: Some("blinks".to_string()),
+
+            weight: Weight {
+                value: 0.5,
+                units: "ounces".to_string(),
+            },
+            tag_ids: None,
+        })
+        .send()
+        .await
+        .map_err(|e| BlinkError::ShipStation(format!("failed to POST /orders/createorder: {e}")))?
+        .json::<ShipStationOrder>()
+        .await
+        .map_err(|e| BlinkError::ShipStation(format!("failed to create order: {e}")))
+        .inspect_err(|_| {
+            log::error!("order #{order_id} was paid with {payment_reference} but not shipped")
+        })?;
+
+    update_order(
+        order_id,
+        UpdateMerchOrder {
+            external_order_id: Some(Some(ssorder.order_id)),
+            payment_method: Some("SOL".to_string()),
+            // TODO: fetch payment amount from chain
+            total_amount_token: Some(1),
+            ..UpdateMerchOrder::default()
+        },
+    )?;
+
+    Ok(merch_order_completed_card(order_id, product_image))
+}
+
+#[get("/nft/<token_id>")]
+pub async fn blink_nft_get(token_id: &str) -> ActionGetResponse {
+    let das_nft_future = get_nft_from_das(token_id);
+
+    let blockchain_ids = get_blockchain_ids();
+    let nft = match get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))
+    {
+        Ok(nft) => nft,
+        Err(e) => {
+            return ActionGetResponse {
+                blockchain_ids,
+                title: "Invalid NFT".to_string(),
+                // TODO: set url for invalid nft
+                // icon: "".to_string(),
+                description: format!("Could not find nft with address {token_id}"),
+                label: "Buy".to_string(),
+                disabled: true,
+                error: Some(e.into()),
+                ..ActionGetResponse::default()
+            };
+        }
+    };
+    let artist_name = get_user_by_wallet_id(&nft.minter_id)
+        .and_then(|artist| artist.username)
+        .unwrap_or(nft.minter_id.clone());
+
+    let das_nft = das_nft_future
+        .await
+        .map_err(|e| log::error!("DAS error for {token_id}: {e}"))
+        .ok();
+
+    let mut links = vec![];
+    let mut status_description = None;
+    let mut error = None;
+
+    let usd_per_sol = get_sol_to_usd_rate().await.unwrap_or_default();
+
+    // if there is a listing, allow buying
+    if let Some(listing) = &nft.listing {
+        let sol_amount = listing.list_price.parse::<f64>().unwrap_or_default();
+        let usd_amount = sol_amount * usd_per_sol;
+        links.push(LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: format!("Buy now for {SOL_SYMBOL}{sol_amount:.2} (~${usd_amount:.2})"),
+            href: uri!(blink_nft_post(
+                token_id = token_id,
+                action = "buy",
+                price = Some(sol_amount),
+                quantity = _
+            ))
+            .to_string(),
+            parameters: vec![],
+        });
+    }
+    // auctions that have ended can't take bids anymore, they only wait for settlement
+    else if let Some(auction_response) = nft.auction.as_ref().filter(|_| is_auction_ended(&nft)) {
+        let ended_at = auction_response.auction.end_time;
+        status_description = Some(match &auction_response.highest_bid {
+            Some(highest_bid) => format!(
+                "Auction ended at {ended_at} UTC, won by {} with {SOL_SYMBOL}{}",
+                get_bidder_name(&highest_bid.bidder),
+                highest_bid.amount
+            ),
+            None => format!("Auction ended at {ended_at} UTC without bids"),
+        });
+        error = Some(BlinkError::AuctionEnded {
+            nft: nft.nft_name.clone(),
+            ended_at,
+        });
+    }
+    // for running auctions, allow placing a minimum or custom bid
+    else if let Some(auction_response) = &nft.auction {
+        let end_time = auction_response.auction.end_time;
+        let time_left = end_time - Utc::now().naive_utc();
+        let bid_count = count_auction_bids(auction_response.auction.id).unwrap_or_default();
+        let ends_in = format!(
+            "Ends in {}h {}m ({end_time} UTC)",
+            time_left.num_hours(),
+            time_left.num_minutes() % 60
+        );
+        status_description = Some(match &auction_response.highest_bid {
+            Some(highest_bid) => format!(
+                "{bid_count} bid(s), {} leads with {SOL_SYMBOL}{}\n{ends_in}",
+                get_bidder_name(&highest_bid.bidder),
+                highest_bid.amount
+            ),
+            None => format!("No bids yet\n{ends_in}"),
+        });
+
+        let minimum_bid = get_minimum_bid(&nft).unwrap_or_default();
+        let usd_amount = minimum_bid * usd_per_sol;
+        links.push(LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: format!("Place bid for {SOL_SYMBOL}{minimum_bid:.2} (~${usd_amount:.2})"),
+            href: uri!(blink_nft_post(
+                token_id = token_id,
+                action = "bid",
+                price = Some(minimum_bid),
+                quantity = _
+            ))
+            .to_string(),
+            parameters: vec![],
+        });
+
+        // custom bid
+        links.push(LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: "Place bid".to_string(),
+            href: format!(
+                "{}?price={{price}}",
+                uri!(blink_nft_post(
+                    token_id = token_id,
+                    action = "bid",
+                    price = _,
+                    quantity = _
+                ))
+            ),
+            parameters: get_price_parameters(minimum_bid),
+        });
+    }
+    // for listed master edition, allow buying a print
+    else if nft.master_edition.is_some() {
+        // unlimited master editions have no max supply
+        let remaining = das_nft.as_ref().and_then(|das_nft| {
+            let supply = &das_nft.result.supply;
+            let max_supply = supply.print_max_supply.map(u64::from)?;
+            let current_supply = supply.print_current_supply.map(u64::from).unwrap_or_default();
+            Some((max_supply.saturating_sub(current_supply), max_supply))
+        });
+        if let Some((remaining, max_supply)) = remaining {
+            status_description = Some(format!("{remaining} of {max_supply} left"));
+            if remaining == 0 {
+                error = Some(BlinkError::SoldOut {
+                    product: nft.nft_name.clone(),
+                    supply: max_supply as i32,
+                });
+            }
+        }
+
+        // a sold out card stays, but a quantity of at least one can't be asked for anymore
+        if !matches!(remaining, Some((0, _))) {
+            let sol_amount =
+                lamports_to_sol(get_print_lamports(&nft, usd_per_sol).unwrap_or_default());
+            let usd_amount = sol_amount * usd_per_sol;
+
+            links.push(LinkedAction {
+                action_type: Some(LinkedActionType::Transaction),
+                label: format!("Buy for {SOL_SYMBOL}{sol_amount:.2} (~${usd_amount:.2})"),
+                href: uri!(blink_nft_post(
+                    token_id = token_id,
+                    action = "buy-print",
+                    price = _,
+                    quantity = Some(1)
+                ))
+                .to_string(),
+                parameters: vec![],
+            });
+            links.push(LinkedAction {
+                action_type: Some(LinkedActionType::Transaction),
+                label: "Buy prints".to_string(),
+                href: format!(
+                    "{}?quantity={{quantity}}",
+                    uri!(blink_nft_post(
+                        token_id = token_id,
+                        action = "buy-print",
+                        price = _,
+                        quantity = _
+                    ))
+                ),
+                parameters: get_print_quantity_parameters(
+                    remaining.map(|(remaining, _)| remaining),
+                ),
+            });
+        }
+    }
+    // finally, allow placing an offer on the nft
+    else {
+        links.push(LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: "Place offer".to_string(),
+            href: format!(
+                "{}?price={{price}}",
+                uri!(blink_nft_post(
+                    token_id = token_id,
+                    action = "place-offer",
+                    price = _,
+                    quantity = _
+                ))
+            ),
+            parameters: get_price_parameters(MINIMUM_OFFER),
+        });
+    }
+
+    links.push(LinkedAction {
+        action_type: Some(LinkedActionType::ExternalLink),
+        label: "View on Foster".to_string(),
+        href: uri!(blink_nft_post(
+            token_id = token_id,
+            action = "view",
+            price = _,
+            quantity = _
+        ))
+        .to_string(),
+        parameters: vec![],
+    });
+
+    // TODO: include product price in sol and usd in the action label
+    ActionGetResponse {
+        blockchain_ids,
+        // TODO: add url for products with missing image
+        icon: get_image_for_nft(&nft).unwrap_or_default(),
+        title: nft.nft_name,
+        // TODO: fetch nft description from chain
+        description: [
+            das_nft
+                .map(|das_nft| das_nft.result.content.metadata.description)
+                .unwrap_or_default(),
+            "".to_string(),
+            format!("nft by {}", artist_name),
+        ]
+        .into_iter()
+        .chain(status_description)
+        .collect::<Vec<_>>()
+        .join("\n"),
+        label: match error {
+            Some(BlinkError::SoldOut { .. }) => "Sold out".to_string(),
+            Some(BlinkError::AuctionEnded { .. }) => "Auction ended".to_string(),
+            _ => String::new(),
+        },
+        disabled: error.is_some(),
+        error: error.map(ActionError::from),
+        links: links.into(),
+        ..ActionGetResponse::default()
+    }
+}
+
+pub const MINIMUM_OFFER: f64 = 0.01;
+
+fn get_print_lamports(nft: &SingleNftResponse, usd_per_sol: f64) -> Option<u64> {
+    let master_edition = nft.master_edition.as_ref()?;
+    let mut lamport_amount = master_edition.price.parse::<u64>().unwrap_or_default();
+    if let Some(merch_product) = &master_edition.merch_product {
+        // +2% slippage
+        lamport_amount += ((merch_product.foster_amount as f64 * 1e7 * 1.02) / usd_per_sol) as u64;
+    }
+    Some(lamport_amount)
+}
+
+fn get_bidder_name(bidder: &str) -> String {
+    get_user_by_wallet_id(bidder)
+        .and_then(|user| user.username)
+        .unwrap_or(bidder.to_string())
+}
+
+fn get_minimum_bid(nft: &SingleNftResponse) -> Option<f64> {
+    let auction_response = nft.auction.as_ref()?;
+    let minimum_bid = if let Some(highest_bid) = &auction_response.highest_bid {
+        let min_bid_increment = auction_response
+            .auction
+            .min_bid_increment
+            .parse::<f64>()
+            .unwrap_or_default();
+        highest_bid.amount.parse::<f64>().unwrap_or_default() + min_bid_increment
+    } else {
+        let mut reserve_price = auction_response
+            .auction
+            .reserve_price
+            .parse::<f64>()
+            .unwrap_or_default();
+        if reserve_price == 0.0 {
+            reserve_price = 0.1;
+        }
+        reserve_price
+    };
+    Some(minimum_bid)
+}
+
+fn get_price_parameters(minimum_price: f64) -> Vec<ActionParameter> {
+    vec![ActionParameter {
+        parameter_type: Some(ActionParameterType::Number),
+        name: "price".to_string(),
+        label: "Custom amount".to_string(),
+        required: true,
+        min: Some(minimum_price),
+        ..ActionParameter::default()
+    }]
+}
+
+// editions are minted in a single legacy transaction of at most 1232 bytes. each edition adds
+// a mint signature, five new accounts and five instructions, so only two fit next to the shared
+// accounts. the size is checked again when the transaction is serialized
+const MAX_PRINTS_PER_TRANSACTION: u32 = 2;
+
+fn get_print_quantity_parameters(remaining: Option<u64>) -> Vec<ActionParameter> {
+    let max = remaining.map_or(MAX_PRINTS_PER_TRANSACTION.into(), |remaining| {
+        remaining.min(MAX_PRINTS_PER_TRANSACTION.into())
+    });
+    vec![ActionParameter {
+        parameter_type: Some(ActionParameterType::Number),
+        name: "quantity".to_string(),
+        label: "Number of prints".to_string(),
+        required: true,
+        min: Some(1.0),
+        max: Some(max as f64),
+        ..ActionParameter::default()
+    }]
+}
+
+fn get_image_for_nft(nft: &SingleNftResponse) -> Option<String> {
+    let image_url = match nft.asset_type.as_ref() {
+        _ if nft.asset_type.as_str().starts_with("video") => nft.cover_image_url.clone(),
+        _ if nft.asset_type.as_str().starts_with("audio") => nft.cover_image_url.clone(),
+        "vr" => nft.cover_image_url.clone(),
+        _ => Some(nft.asset_url.clone()),
+    };
+    image_url.map(|url| format!("https://cdn.helius-rpc.com/cdn-cgi/image/quality=75/{url}"))
+}
+
+#[post(
+    "/nft/<token_id>/<action>?<price>&<quantity>",
+    format = "application/json",
+    data = "<request>",
+    rank = 1
+)]
+pub async fn blink_nft_post(
+    token_id: &str,
+    action: &str,
+    price: Option<f64>,
+    quantity: Option<u32>,
+    request: Json<ActionPostRequest<'_, Option<NftActionBlinkData>>>,
+) -> Result<ActionPostResponse, BlinkError> {
+    let NftActionBlinkData {
+        price: request_price,
+        quantity: request_quantity,
+    } = match &request.data {
+        Some(data) => data,
+        None => &NftActionBlinkData::default(),
+    };
+
+    let effective_price = resolve_price(price, *request_price)?;
+
+    let parameters = match action {
+        // bids may have come in since GET, so the minimum is checked against the highest bid now
+        "bid" => {
+            let nft = get_single_nft_response(token_id)
+                .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+            get_price_parameters(get_minimum_bid(&nft).ok_or_else(|| {
+                BlinkError::InvalidRequest(format!("nft {token_id} is not on auction"))
+            })?)
+        }
+        "place-offer" => get_price_parameters(MINIMUM_OFFER),
+        _ => vec![],
+    };
+    let values = effective_price
+        .map(|price| ("price", price.to_string()))
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    validate_action_parameters(&parameters, &values)?;
+
+    let response = match action {
+        "buy-print" => {
+            let quantity = resolve_quantity(quantity, *request_quantity)?.unwrap_or(1);
+            create_nft_print_response(token_id, quantity, request.account).await?
+        }
+        "view" => ActionPostResponse {
+            blockchain_ids: get_blockchain_ids(),
+            kind: ActionPostResponseKind::ExternalLink {
+                external_link: format!("{}/nft/{token_id}", get_marketplace_url()),
+            },
+            message: None,
+            links: None,
+        },
+        "buy" => create_nft_buy_response(token_id, effective_price, request.account).await?,
+        "bid" => create_nft_bid_response(token_id, effective_price, request.account).await?,
+        "place-offer" => {
+            create_nft_offer_response(token_id, effective_price, request.account).await?
+        }
+        _ => return Err(BlinkError::UnknownAction(action.to_string())),
+    };
+
+    Ok(response)
+}
+
+async fn create_nft_print_response(
+    token_id: &str,
+    quantity: u32,
+    account: &str,
+) -> Result<ActionPostResponse, BlinkError> {
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    let das_nft = get_nft_from_das(token_id)
+        .await
+        .map_err(|e| BlinkError::Das(e.to_string()))?;
+
+    // unlimited master editions have no max supply
+    let supply = &das_nft.result.supply;
+    let remaining = supply.print_max_supply.map(|max_supply| {
+        u64::from(max_supply)
+            .saturating_sub(supply.print_current_supply.map(u64::from).unwrap_or_default())
+    });
+    if remaining == Some(0) {
+        return Err(BlinkError::SoldOut {
+            product: nft.nft_name,
+            supply: supply.print_max_supply.unwrap_or_default() as i32,
+        });
+    }
+    let values = HashMap::from([("quantity", quantity.to_string())]);
+    validate_action_parameters(&get_print_quantity_parameters(remaining), &values)?;
+
+    let prints = create_print_instructions(token_id, account, quantity).await?;
+    if prints.len() != quantity as usize {
+        return Err(BlinkError::Internal(format!(
+            "expected {quantity} editions of {token_id}, got {}",
+            prints.len()
+        )));
+    }
+
+    let edition_numbers = prints
+        .iter()
+        .map(|print_info| format!("#{}", print_info.edition_number))
+        .collect::<Vec<_>>();
+    let edition_mints = prints
+        .iter()
+        .map(|print_info| print_info.edition_mint.clone())
+        .collect::<Vec<_>>();
+
+    // all editions land together, so the callback can index them as one purchase
+    let (print_instructions, signers): (Vec<_>, Vec<_>) = prints
+        .into_iter()
+        .map(|print_info| (print_info.instructions, print_info.signers))
+        .unzip();
+    let signers = signers.into_iter().flatten().collect::<Vec<_>>();
+    let transaction = create_print_transaction(account, print_instructions, &signers).await?;
+
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Transaction { transaction },
+        message: Some(match edition_numbers.len() {
+            1 => format!("Minting Print Edition {}", edition_numbers[0]),
+            _ => format!("Minting Print Editions {}", edition_numbers.join(", ")),
+        }),
+        // unlike merch orders nothing else records prints, so the callback is always needed
+        links: Some(ActionPostLinks {
+            next: NextAction::Post {
+                href: uri!(blink_nft_index_print_post(
+                    token_ids = edition_mints.join(","),
+                    token_id = Some(token_id)
+                ))
+                .to_string(),
+            },
+        }),
+    })
+}
+
+fn print_pending_card(nft: &SingleNftResponse, token_ids: &str) -> ActionGetResponse {
+    ActionGetResponse {
+        blockchain_ids: get_blockchain_ids(),
+        icon: get_image_for_nft(nft).unwrap_or_default(),
+        title: nft.nft_name.clone(),
+        description: "Waiting for your prints to confirm, this can take a few seconds."
+            .to_string(),
+        label: "Mint pending".to_string(),
+        links: vec![LinkedAction {
+            action_type: Some(LinkedActionType::Post),
+            label: "Check mint status".to_string(),
+            href: uri!(blink_nft_print_status_post(
+                token_id = &nft.token_id,
+                token_ids = token_ids
+            ))
+            .to_string(),
+            parameters: vec![],
+        }]
+        .into(),
+        ..ActionGetResponse::default()
+    }
+}
+
+// the `price` query param and the price in the request data are both filled from the same
+// user input, so either is accepted on its own but they must agree when both are sent.
+// listing prices are still checked against the server side listing by the action itself
+fn resolve_price(query: Option<f64>, data: Option<f64>) -> Result<Option<f64>, BlinkError> {
+    match (query, data) {
+        (Some(query), Some(data)) if sol_to_lamports(query) != sol_to_lamports(data) => {
+            Err(BlinkError::PriceMismatch { query, data })
+        }
+        (query, data) => Ok(query.or(data)),
+    }
+}
+
+// same rule as `resolve_price`, quantities are only sent by the multi print link
+fn resolve_quantity(query: Option<u32>, data: Option<u32>) -> Result<Option<u32>, BlinkError> {
+    match (query, data) {
+        (Some(query), Some(data)) if query != data => {
+            Err(BlinkError::QuantityMismatch { query, data })
+        }
+        (query, data) => Ok(query.or(data)),
+    }
+}
+
+async fn create_nft_buy_response(
+    token_id: &str,
+    price: Option<f64>,
+    account: &str,
+) -> Result<ActionPostResponse, BlinkError> {
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    let listing = nft.listing.as_ref().ok_or_else(|| BlinkError::NotListed {
+        nft: nft.nft_name.clone(),
+    })?;
+    let list_price = listing
+        .list_price
+        .parse::<f64>()
+        .map_err(|e| format!("invalid list price {}: {e}", listing.list_price))?;
+
+    // the buy link carries the price shown on GET, so a relisting can't be bought unseen
+    let price = price.ok_or_else(|| BlinkError::InvalidRequest("missing price".to_string()))?;
+    if sol_to_lamports(price) != sol_to_lamports(list_price) {
+        return Err(BlinkError::PriceChanged {
+            expected: price,
+            actual: list_price,
+        });
+    }
+
+    let buyer = validate_public_key(account).map_err(BlinkError::InvalidPubkey)?;
+    if buyer == nft.owner_id {
+        return Err(BlinkError::InvalidRequest("you already own this nft".to_string()));
+    }
+
+    let price_lamports = sol_to_lamports(list_price);
+    assert_minimum_balance(buyer, price_lamports + 20_000)
+        .await
+        .map_err(BlinkError::InsufficientFunds)?;
+
+    let transaction =
+        create_buy_listing_transaction(buyer, &nft.owner_id, token_id, price_lamports).await?;
+
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Transaction { transaction },
+        message: Some(format!("Buying {} for {SOL_SYMBOL}{list_price:.2}", nft.nft_name)),
+        links: Some(ActionPostLinks {
+            next: NextAction::Post {
+                href: uri!(blink_nft_buy_complete_post(token_id = token_id)).to_string(),
+            },
+        }),
+    })
+}
+
+#[post("/nft/<token_id>/buy/complete", data = "<request>")]
+pub async fn blink_nft_buy_complete_post(
+    token_id: &str,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionGetResponse, BlinkError> {
+    let signature = request
+        .signature
+        .ok_or_else(|| BlinkError::InvalidRequest("missing signature".to_string()))?;
+    confirm_buy_listing_transaction(signature, request.account, token_id)
+        .await
+        .map_err(BlinkError::InvalidPayment)?;
+
+    let das_nft = get_nft_from_das(token_id)
+        .await
+        .map_err(|e| BlinkError::Das(e.to_string()))?;
+    if das_nft.result.ownership.owner != request.account {
+        return Err(BlinkError::InvalidPayment(format!(
+            "{token_id} is owned by {}, not {}",
+            das_nft.result.ownership.owner, request.account
+        )));
+    }
+
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    // the listing is closed by the purchase, so the sale is only recorded once
+    if nft.owner_id != request.account {
+        complete_listing_sale(token_id, request.account, signature)?;
+    }
+
+    Ok(ActionGetResponse {
+        blockchain_ids: get_blockchain_ids(),
+        action_type: BlinkActionType::Completed,
+        icon: get_image_for_nft(&nft).unwrap_or_default(),
+        title: nft.nft_name,
+        description: das_nft.result.content.metadata.description,
+        label: "NFT bought successfully!".to_string(),
+        disabled: true,
+        ..ActionGetResponse::default()
+    })
+}
+
+async fn create_nft_bid_response(
+    token_id: &str,
+    price: Option<f64>,
+    account: &str,
+) -> Result<ActionPostResponse, BlinkError> {
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    let auction_response = nft.auction.as_ref().ok_or_else(|| {
+        BlinkError::InvalidRequest(format!("{} is not on auction", nft.nft_name))
+    })?;
+    if Utc::now().naive_utc() > auction_response.auction.end_time {
+        return Err(BlinkError::AuctionEnded {
+            nft: nft.nft_name.clone(),
+            ended_at: auction_response.auction.end_time,
+        });
+    }
+
+    // the minimum bid was already checked against the current highest bid by `blink_nft_post`
+    let bid = price.ok_or_else(|| BlinkError::InvalidRequest("missing price".to_string()))?;
+
+    let bidder = validate_public_key(account).map_err(BlinkError::InvalidPubkey)?;
+    if bidder == nft.owner_id {
+        return Err(BlinkError::InvalidRequest("you can't bid on your own nft".to_string()));
+    }
+
+    let bid_lamports = sol_to_lamports(bid);
+    assert_minimum_balance(bidder, bid_lamports + 20_000)
+        .await
+        .map_err(BlinkError::InsufficientFunds)?;
+
+    let transaction = create_bid_transaction(bidder, token_id, bid_lamports).await?;
+
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Transaction { transaction },
+        message: Some(format!("Bidding {SOL_SYMBOL}{bid:.2} on {}", nft.nft_name)),
+        links: Some(ActionPostLinks {
+            next: NextAction::Post {
+                href: uri!(blink_nft_bid_complete_post(token_id = token_id)).to_string(),
+            },
+        }),
+    })
+}
+
+#[post("/nft/<token_id>/bid/complete", data = "<request>")]
+pub async fn blink_nft_bid_complete_post(
+    token_id: &str,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionGetResponse, BlinkError> {
+    let signature = request
+        .signature
+        .ok_or_else(|| BlinkError::InvalidRequest("missing signature".to_string()))?;
+    // the amount is read from the bid itself, the callback url can't be trusted
+    let amount = confirm_bid_transaction(signature, request.account, token_id)
+        .await
+        .map(lamports_to_sol)
+        .map_err(BlinkError::InvalidPayment)?;
+
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    let auction_response = nft.auction.as_ref().ok_or_else(|| {
+        BlinkError::InvalidRequest(format!("{} is not on auction", nft.nft_name))
+    })?;
+    let outbid_bidder = auction_response
+        .highest_bid
+        .as_ref()
+        .map(|highest_bid| highest_bid.bidder.clone())
+        .filter(|bidder| bidder != request.account);
+
+    // only the first callback for a transaction records the bid and notifies the previous leader
+    let created = create_auction_bid_once(NewAuctionBid {
+        auction_id: auction_response.auction.id,
+        bidder: request.account,
+        amount: &amount.to_string(),
+        transaction_id: signature,
+    })?;
+    if !created {
+        return Err(BlinkError::AlreadyRecorded {
+            transaction_id: signature.to_string(),
+        });
+    }
+
+    // let the previous leader know they can bid again
+    if let Some(outbid_bidder) = outbid_bidder {
+        foster_notification::outbid(&outbid_bidder, &nft, amount);
+    }
+
+    Ok(ActionGetResponse {
+        blockchain_ids: get_blockchain_ids(),
+        action_type: BlinkActionType::Completed,
+        icon: get_image_for_nft(&nft).unwrap_or_default(),
+        title: nft.nft_name,
+        description: format!("You are the highest bidder with {SOL_SYMBOL}{amount:.2}"),
+        label: "Bid placed successfully!".to_string(),
+        disabled: true,
+        ..ActionGetResponse::default()
+    })
+}
+
+async fn create_nft_offer_response(
+    token_id: &str,
+    price: Option<f64>,
+    account: &str,
+) -> Result<ActionPostResponse, BlinkError> {
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    let offer = price.ok_or_else(|| BlinkError::InvalidRequest("missing price".to_string()))?;
+
+    let bidder = validate_public_key(account).map_err(BlinkError::InvalidPubkey)?;
+    if bidder == nft.owner_id {
+        return Err(BlinkError::InvalidRequest(
+            "you can't make an offer on your own nft".to_string(),
+        ));
+    }
+
+    let offer_lamports = sol_to_lamports(offer);
+    assert_minimum_balance(bidder, offer_lamports + 20_000)
+        .await
+        .map_err(BlinkError::InsufficientFunds)?;
+
+    let transaction = create_offer_transaction(bidder, token_id, offer_lamports).await?;
+
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Transaction { transaction },
+        message: Some(format!(
+            "Offering {SOL_SYMBOL}{offer:.2} for {}, held in escrow until accepted",
+            nft.nft_name
+        )),
+        links: Some(ActionPostLinks {
+            next: NextAction::Post {
+                href: uri!(blink_nft_offer_complete_post(token_id = token_id)).to_string(),
+            },
+        }),
+    })
+}
+
+#[post("/nft/<token_id>/offer/complete", data = "<request>")]
+pub async fn blink_nft_offer_complete_post(
+    token_id: &str,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionGetResponse, BlinkError> {
+    let signature = request
+        .signature
+        .ok_or_else(|| BlinkError::InvalidRequest("missing signature".to_string()))?;
+    // the amount is read from the escrow itself, the callback url can't be trusted
+    let amount = confirm_offer_transaction(signature, request.account, token_id)
+        .await
+        .map(lamports_to_sol)
+        .map_err(BlinkError::InvalidPayment)?;
+
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    // concurrent callbacks for the same escrow record it once
+    let created = create_offer_once(NewOffer {
+        token_id,
+        bidder: request.account,
+        amount: &amount.to_string(),
+        transaction_id: signature,
+    })?;
+    if !created {
+        return Err(BlinkError::AlreadyRecorded {
+            transaction_id: signature.to_string(),
+        });
+    }
+
+    Ok(ActionGetResponse {
+        blockchain_ids: get_blockchain_ids(),
+        action_type: BlinkActionType::Completed,
+        icon: get_image_for_nft(&nft).unwrap_or_default(),
+        title: nft.nft_name,
+        description: format!(
+            "Your offer of {SOL_SYMBOL}{amount:.2} is in escrow until the owner accepts it"
+        ),
+        label: "Offer placed successfully!".to_string(),
+        disabled: true,
+        ..ActionGetResponse::default()
+    })
+}
+
+pub const MAX_LISTED_OFFERS: usize = 5;
+
+// companion blink for the owner, accepting is only allowed for the owner's wallet on POST
+// ranked like the manage blink, the path could also be a merch item of an artist named "nft"
+#[get("/nft/<token_id>/offers", rank = 1)]
+pub async fn blink_nft_offers_get(token_id: &str) -> ActionGetResponse {
+    let blockchain_ids = get_blockchain_ids();
+    let nft = match get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))
+    {
+        Ok(nft) => nft,
+        Err(e) => {
+            return ActionGetResponse {
+                blockchain_ids,
+                title: "Invalid NFT".to_string(),
+                description: format!("Could not find nft with address {token_id}"),
+                label: "Accept offer".to_string(),
+                disabled: true,
+                error: Some(e.into()),
+                ..ActionGetResponse::default()
+            };
+        }
+    };
+
+    let mut offers = match get_offers_for_nft(token_id) {
+        Ok(offers) => offers,
+        Err(e) => {
+            return ActionGetResponse {
+                blockchain_ids,
+                icon: get_image_for_nft(&nft).unwrap_or_default(),
+                title: nft.nft_name,
+                label: "Accept offer".to_string(),
+                disabled: true,
+                error: Some(BlinkError::Internal(e).into()),
+                ..ActionGetResponse::default()
+            };
+        }
+    };
+    offers.retain(|offer| offer.status == "open");
+    offers.sort_by(|a, b| {
+        let a = a.amount.parse::<f64>().unwrap_or_default();
+        let b = b.amount.parse::<f64>().unwrap_or_default();
+        b.total_cmp(&a)
+    });
+
+    let usd_per_sol = get_sol_to_usd_rate().await.unwrap_or_default();
+    let links = offers
+        .iter()
+        .take(MAX_LISTED_OFFERS)
+        .map(|offer| {
+            let sol_amount = offer.amount.parse::<f64>().unwrap_or_default();
+            let usd_amount = sol_amount * usd_per_sol;
+            let bidder_name = get_bidder_name(&offer.bidder);
+            LinkedAction {
+                action_type: Some(LinkedActionType::Transaction),
+                label: format!(
+                    "Accept {SOL_SYMBOL}{sol_amount:.2} (~${usd_amount:.2}) from {bidder_name}"
+                ),
+                href: uri!(blink_nft_accept_offer_post(token_id = token_id, offer_id = offer.id))
+                    .to_string(),
+                parameters: vec![],
+            }
+        })
+        .collect::<Vec<_>>();
+
+    ActionGetResponse {
+        blockchain_ids,
+        icon: get_image_for_nft(&nft).unwrap_or_default(),
+        description: match offers.len() {
+            0 => "There are no offers yet".to_string(),
+            count => format!("{count} open offer(s) on {}", nft.nft_name),
+        },
+        title: nft.nft_name,
+        label: "Accept offer".to_string(),
+        disabled: links.is_empty(),
+        links: links.into(),
+        ..ActionGetResponse::default()
+    }
+}
+
+#[post(
+    "/nft/<token_id>/offers/<offer_id>/accept",
+    format = "application/json",
+    data = "<request>"
+)]
+pub async fn blink_nft_accept_offer_post(
+    token_id: &str,
+    offer_id: i32,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionPostResponse, BlinkError> {
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    let owner = validate_public_key(request.account).map_err(BlinkError::InvalidPubkey)?;
+    if owner != nft.owner_id {
+        return Err(BlinkError::NotOwner {
+            nft: nft.nft_name.clone(),
+        });
+    }
+
+    let offer = get_offer(offer_id).map_err(BlinkError::not_found(format!("offer {offer_id}")))?;
+    if offer.token_id != token_id || offer.status != "open" {
+        return Err(BlinkError::InvalidRequest(format!("offer {offer_id} is no longer open")));
+    }
+    let offer_amount = offer
+        .amount
+        .parse::<f64>()
+        .map_err(|e| format!("invalid offer amount {}: {e}", offer.amount))?;
+
+    let transaction = create_accept_offer_transaction(
+        owner,
+        &offer.bidder,
+        token_id,
+        sol_to_lamports(offer_amount),
+    )
+    .await?;
+
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Transaction { transaction },
+        message: Some(format!("Selling {} for {SOL_SYMBOL}{offer_amount:.2}", nft.nft_name)),
+        links: Some(ActionPostLinks {
+            next: NextAction::Post {
+                href: uri!(blink_nft_offer_accepted_post(token_id = token_id, offer_id = offer_id))
+                    .to_string(),
+            },
+        }),
+    })
+}
+
+#[post("/nft/<token_id>/offers/<offer_id>/accepted", data = "<request>")]
+pub async fn blink_nft_offer_accepted_post(
+    token_id: &str,
+    offer_id: i32,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionGetResponse, BlinkError> {
+    let signature = request
+        .signature
+        .ok_or_else(|| BlinkError::InvalidRequest("missing signature".to_string()))?;
+
+    // the nft only changes owner here, so the same checks as on accept still hold
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    if request.account != nft.owner_id {
+        return Err(BlinkError::NotOwner { nft: nft.nft_name });
+    }
+    let offer = get_offer(offer_id).map_err(BlinkError::not_found(format!("offer {offer_id}")))?;
+    if offer.token_id != token_id || offer.status != "open" {
+        return Err(BlinkError::InvalidRequest(format!("offer {offer_id} is no longer open")));
+    }
+    let offer_amount = offer
+        .amount
+        .parse::<f64>()
+        .map_err(|e| format!("invalid offer amount {}: {e}", offer.amount))?;
+
+    let accepted_lamports =
+        confirm_accept_offer_transaction(signature, request.account, &offer.bidder, token_id)
+            .await
+            .map_err(BlinkError::InvalidPayment)?;
+    if accepted_lamports != sol_to_lamports(offer_amount) {
+        return Err(BlinkError::InvalidPayment(format!(
+            "transaction {signature} accepted {} instead of offer {offer_id} of {offer_amount}",
+            lamports_to_sol(accepted_lamports)
+        )));
+    }
+    accept_offer(offer_id, request.account, signature)?;
+
+    Ok(ActionGetResponse {
+        blockchain_ids: get_blockchain_ids(),
+        action_type: BlinkActionType::Completed,
+        icon: get_image_for_nft(&nft).unwrap_or_default(),
+        title: nft.nft_name,
+        label: "Offer accepted!".to_string(),
+        disabled: true,
+        ..ActionGetResponse::default()
+    })
+}
+
+fn is_auction_ended(nft: &SingleNftResponse) -> bool {
+    nft.auction
+        .as_ref()
+        .is_some_and(|auction_response| Utc::now().naive_utc() > auction_response.auction.end_time)
+}
+
+// owner side blink, every action checks the connected wallet owns the nft on POST
+// ranked like `blink_nft_post`, the path could also be a merch item of an artist named "nft"
+#[get("/nft/<token_id>/manage", rank = 1)]
+pub async fn blink_nft_manage_get(token_id: &str) -> ActionGetResponse {
+    let blockchain_ids = get_blockchain_ids();
+    let nft = match get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))
+    {
+        Ok(nft) => nft,
+        Err(e) => {
+            return ActionGetResponse {
+                blockchain_ids,
+                title: "Invalid NFT".to_string(),
+                description: format!("Could not find nft with address {token_id}"),
+                label: "Manage".to_string(),
+                disabled: true,
+                error: Some(e.into()),
+                ..ActionGetResponse::default()
+            };
+        }
+    };
+
+    let mut links = vec![];
+    let mut description = vec![];
+
+    if let Some(auction_response) = &nft.auction {
+        if is_auction_ended(&nft) {
+            links.push(LinkedAction {
+                action_type: Some(LinkedActionType::Transaction),
+                label: "Settle auction".to_string(),
+                href: uri!(blink_nft_manage_post(
+                    token_id = token_id,
+                    action = "settle",
+                    price = _
+                ))
+                .to_string(),
+                parameters: vec![],
+            });
+        } else {
+            description.push(format!("Auction ends at {} UTC", auction_response.auction.end_time));
+        }
+    } else if let Some(listing) = &nft.listing {
+        description.push(format!("Listed for {SOL_SYMBOL}{}", listing.list_price));
+        links.push(LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: "Change price".to_string(),
+            href: format!(
+                "{}?price={{price}}",
+                uri!(blink_nft_manage_post(
+                    token_id = token_id,
+                    action = "list",
+                    price = _
+                ))
+            ),
+            parameters: get_price_parameters(MINIMUM_OFFER),
+        });
+        links.push(LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: "Cancel listing".to_string(),
+            href: uri!(blink_nft_manage_post(
+                token_id = token_id,
+                action = "delist",
+                price = _
+            ))
+            .to_string(),
+            parameters: vec![],
+        });
+    } else {
+        links.push(LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: "List".to_string(),
+            href: format!(
+                "{}?price={{price}}",
+                uri!(blink_nft_manage_post(
+                    token_id = token_id,
+                    action = "list",
+                    price = _
+                ))
+            ),
+            parameters: get_price_parameters(MINIMUM_OFFER),
+        });
+    }
+
+    // offers stay in escrow while the nft is listed, so the best one can always be accepted
+    let best_offer = get_offers_for_nft(token_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|offer| offer.status == "open")
+        .max_by(|a, b| {
+            let a = a.amount.parse::<f64>().unwrap_or_default();
+            let b = b.amount.parse::<f64>().unwrap_or_default();
+            a.total_cmp(&b)
+        });
+    if let Some(offer) = best_offer {
+        links.push(LinkedAction {
+            action_type: Some(LinkedActionType::Transaction),
+            label: format!("Accept best offer of {SOL_SYMBOL}{}", offer.amount),
+            href: uri!(blink_nft_accept_offer_post(token_id = token_id, offer_id = offer.id))
+                .to_string(),
+            parameters: vec![],
+        });
+    }
+
+    ActionGetResponse {
+        blockchain_ids,
+        icon: get_image_for_nft(&nft).unwrap_or_default(),
+        title: nft.nft_name,
+        description: description.join("\n"),
+        label: "Manage".to_string(),
+        links: links.into(),
+        ..ActionGetResponse::default()
+    }
+}
+
+#[post(
+    "/nft/<token_id>/manage/<action>?<price>",
+    format = "application/json",
+    data = "<request>",
+    rank = 1
+)]
+pub async fn blink_nft_manage_post(
+    token_id: &str,
+    action: &str,
+    price: Option<f64>,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionPostResponse, BlinkError> {
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    let owner = validate_public_key(request.account).map_err(BlinkError::InvalidPubkey)?;
+    if owner != nft.owner_id {
+        return Err(BlinkError::NotOwner { nft: nft.nft_name });
+    }
+
+    let (transaction, message) = match action {
+        "list" => {
+            let values = price
+                .map(|price| ("price", price.to_string()))
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+            validate_action_parameters(&get_price_parameters(MINIMUM_OFFER), &values)?;
+            if nft.auction.is_some() {
+                return Err(BlinkError::InvalidRequest(format!("{} is on auction", nft.nft_name)));
+            }
+            let price = price.unwrap_or_default();
+            (
+                create_list_transaction(owner, token_id, sol_to_lamports(price)).await?,
+                format!("Listing {} for {SOL_SYMBOL}{price:.2}", nft.nft_name),
+            )
+        }
+        "delist" => {
+            if nft.listing.is_none() {
+                return Err(BlinkError::NotListed { nft: nft.nft_name });
+            }
+            (
+                create_delist_transaction(owner, token_id).await?,
+                format!("Cancelling listing of {}", nft.nft_name),
+            )
+        }
+        "settle" => {
+            if !is_auction_ended(&nft) {
+                return Err(BlinkError::InvalidRequest(format!(
+                    "auction for {} has not ended",
+                    nft.nft_name
+                )));
+            }
+            (
+                create_settle_auction_transaction(owner, token_id).await?,
+                format!("Settling auction of {}", nft.nft_name),
+            )
+        }
+        _ => return Err(BlinkError::UnknownAction(action.to_string())),
+    };
 
-            weight: Weight {
-                value: 0.5,
-                units: "ounces".to_string(),
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Transaction { transaction },
+        message: Some(message),
+        links: Some(ActionPostLinks {
+            next: NextAction::Post {
+                href: uri!(blink_nft_manage_complete_post(token_id = token_id, action = action))
+                    .to_string(),
             },
-            tag_ids: None,
-        })
-        .send()
-        .await
-        .map_err(|e| format!("failed to POST /orders/createorder: {e}"))?
-        .json::<ShipStationOrder>()
-        .await
-        .map_err(|e| format!("failed to create order: {e}"))?;
+        }),
+    })
+}
 
-    update_order(
-        order_id,
-        UpdateMerchOrder {
-            external_order_id: Some(Some(ssorder.order_id)),
-            transaction_id: Some(Some(payment_reference.to_string())),
-            payment_method: Some("SOL".to_string()),
-            // TODO: fetch payment amount from chain
-            total_amount_token: Some(1),
-            ..UpdateMerchOrder::default()
-        },
-    )?;
+#[post("/nft/<token_id>/manage/<action>/complete", data = "<request>")]
+pub async fn blink_nft_manage_complete_post(
+    token_id: &str,
+    action: &str,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionGetResponse, BlinkError> {
+    let signature = request
+        .signature
+        .ok_or_else(|| BlinkError::InvalidRequest("missing signature".to_string()))?;
+
+    let nft = get_single_nft_response(token_id)
+        .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+    if request.account != nft.owner_id {
+        return Err(BlinkError::NotOwner { nft: nft.nft_name });
+    }
+
+    let label = match (action, &nft.auction) {
+        ("list" | "delist", _) => {
+            let confirmed = match action {
+                "list" => confirm_list_transaction(signature, request.account, token_id).await,
+                _ => confirm_delist_transaction(signature, request.account, token_id).await,
+            };
+            confirmed.map_err(BlinkError::InvalidPayment)?;
+            // the listing is read back from the chain, so replaying an older signature can't
+            // change the stored price
+            match get_listing_lamports(token_id).await? {
+                Some(price_lamports) => {
+                    let price = lamports_to_sol(price_lamports);
+                    upsert_listing(token_id, request.account, &price.to_string(), signature)?;
+                    format!("Listed for {SOL_SYMBOL}{price:.2}")
+                }
+                None => {
+                    close_listing(token_id, signature)?;
+                    "Listing cancelled".to_string()
+                }
+            }
+        }
+        ("settle", Some(auction_response)) => {
+            confirm_settle_auction_transaction(signature, request.account, token_id)
+                .await
+                .map_err(BlinkError::InvalidPayment)?;
+            settle_auction(auction_response.auction.id, signature)?;
+            "Auction settled".to_string()
+        }
+        _ => return Err(BlinkError::UnknownAction(action.to_string())),
+    };
 
     Ok(ActionGetResponse {
-        blockchain_id: get_blockchain_id(),
+        blockchain_ids: get_blockchain_ids(),
         action_type: BlinkActionType::Completed,
-        title: format!("Order #{order_id}"),
-        // TODO: show confetti GIF
-        icon: product_image,
-        description: format!(
-            "Manage your order at {}/orders/{order_id}",
-            match network.as_str() {
-                "mainnet" => "https://fostermarketplace.app",
-                _ => "https://devnet.fostermarketplace.app",
-            }
-        ),
-        label: "Order placed successfully!".to_string(),
+        icon: get_image_for_nft(&nft).unwrap_or_default(),
+        title: nft.nft_name,
+        label,
         disabled: true,
         ..ActionGetResponse::default()
     })
 }
 
-#[get("/nft/<token_id>")]
-pub async fn blink_nft_get(token_id: &str) -> ActionGetResponse {
-    let das_nft_future = get_nft_from_das(token_id);
+pub const MAX_LISTED_AUCTIONS: usize = 3;
 
-    let blockchain_id = get_blockchain_id();
-    let nft = match get_single_nft_response(token_id) {
-        Ok(nft) => nft,
+#[get("/collection/<collection_id>")]
+pub async fn blink_collection_get(
+    collection_id: &str,
+    version: ActionVersion,
+) -> ActionGetResponse {
+    let blockchain_ids = get_blockchain_ids();
+    let (collection, nfts) = match get_collection(collection_id)
+        .and_then(|collection| Ok((collection, get_nfts_by_collection(collection_id)?)))
+        .map_err(BlinkError::not_found(format!("collection {collection_id}")))
+    {
+        Ok(collection) => collection,
         Err(e) => {
             return ActionGetResponse {
-                blockchain_id,
-                title: "Invalid NFT".to_string(),
-                // TODO: set url for invalid nft
-                // icon: "".to_string(),
-                description: format!("Could not find nft with address {token_id}"),
+                blockchain_ids,
+                title: "Invalid Collection".to_string(),
+                description: format!("Could not find collection with id {collection_id}"),
                 label: "Buy".to_string(),
                 disabled: true,
                 error: Some(e.into()),
@@ -512,268 +2851,408 @@ pub async fn blink_nft_get(token_id: &str) -> ActionGetResponse {
             };
         }
     };
-    let artist_name = get_user_by_wallet_id(&nft.minter_id)
-        .and_then(|artist| artist.username)
-        .unwrap_or(nft.minter_id.clone());
 
+    let usd_per_sol = get_sol_to_usd_rate().await.unwrap_or_default();
     let mut links = vec![];
+    let mut description = vec![collection.description, "".to_string()];
 
-    let usd_per_sol = get_sol_to_usd_rate().await.unwrap_or_default();
+    let listed = nfts
+        .iter()
+        .filter_map(|nft| {
+            let listing = nft.listing.as_ref()?;
+            Some((nft, listing.list_price.parse::<f64>().ok()?))
+        })
+        .collect::<Vec<_>>();
+    description.push(format!("{} items, {} listed", nfts.len(), listed.len()));
 
-    // if there is a listing, allow buying
-    if let Some(listing) = &nft.listing {
-        let sol_amount = listing.list_price.parse::<f64>().unwrap_or_default();
-        let usd_amount = sol_amount * usd_per_sol;
+    // buying goes through the nft blink, so the listing price is checked the same way
+    if let Some((floor_nft, floor_price)) =
+        listed.into_iter().min_by(|(_, a), (_, b)| a.total_cmp(b))
+    {
+        let usd_amount = floor_price * usd_per_sol;
+        description.push(format!("Floor {SOL_SYMBOL}{floor_price:.2}"));
         links.push(LinkedAction {
-            label: format!("Buy now for {SOL_SYMBOL}{sol_amount:.2} (~${usd_amount:.2})"),
+            action_type: Some(LinkedActionType::Transaction),
+            label: format!("Buy floor for {SOL_SYMBOL}{floor_price:.2} (~${usd_amount:.2})"),
             href: uri!(blink_nft_post(
-                token_id = token_id,
+                token_id = &floor_nft.token_id,
                 action = "buy",
-                price = _
+                price = Some(floor_price),
+                quantity = _
             ))
             .to_string(),
             parameters: vec![],
         });
     }
-    // for auctions, allow placing a minimum or custom bid
-    else if let Some(auction_response) = &nft.auction {
-        // minimum bid
-        let minimum_bid = if let Some(highest_bid) = &auction_response.highest_bid {
-            // TODO: save min_bid_increment to db
-            highest_bid.amount.parse::<f64>().unwrap_or_default() + 0.01
-        } else {
-            let mut reserve_price = auction_response
-                .auction
-                .reserve_price
-                .parse::<f64>()
-                .unwrap_or_default();
-            if reserve_price == 0.0 {
-                reserve_price = 0.1;
-            }
-            reserve_price
-        };
-        let usd_amount = minimum_bid * usd_per_sol;
-        links.push(LinkedAction {
-            label: format!("Place bid for {SOL_SYMBOL}{minimum_bid:.2} (~${usd_amount:.2})"),
-            href: uri!(blink_nft_post(
-                token_id = token_id,
-                action = "bid",
-                price = Some(minimum_bid)
-            ))
-            .to_string(),
-            parameters: vec![],
-        });
 
-        // custom bid
-        links.push(LinkedAction {
-            label: "Place bid".to_string(),
-            href: uri!(blink_nft_post(
-                token_id = token_id,
-                action = "bid",
-                price = _
-            ))
-            .to_string(),
-            parameters: vec![ActionParameter {
-                parameter_type: "number".to_string(),
-                name: "price".to_string(),
-                label: "Custom amount".to_string(),
-                required: true,
-                min: Some(minimum_bid),
-                ..ActionParameter::default()
-            }],
-        });
-    }
-    // for listed master edition, allow buying a print
-    else if let Some(master_edition) = &nft.master_edition {
-        let mut lamport_amount = master_edition.price.parse::<u64>().unwrap_or_default();
-        if let Some(merch_product) = &master_edition.merch_product {
-            // +2% slippage
-            lamport_amount +=
-                ((merch_product.foster_amount as f64 * 1e7 * 1.02) / usd_per_sol) as u64;
+    // the cheapest print that isn't sold out, supplies are only fetched until one has prints left
+    let mut prints = nfts
+        .iter()
+        .filter_map(|nft| Some((nft, get_print_lamports(nft, usd_per_sol)?)))
+        .collect::<Vec<_>>();
+    prints.sort_by_key(|(_, print_lamports)| *print_lamports);
+    let mut cheapest_print = None;
+    for (nft, print_lamports) in prints {
+        let Ok(das_nft) = get_nft_from_das(&nft.token_id).await else {
+            continue;
+        };
+        // unlimited master editions have no max supply
+        let supply = &das_nft.result.supply;
+        let current_supply = supply.print_current_supply.map(u64::from).unwrap_or_default();
+        if supply
+            .print_max_supply
+            .is_some_and(|max_supply| current_supply >= u64::from(max_supply))
+        {
+            continue;
         }
-
-        let sol_amount = lamports_to_sol(lamport_amount);
+        cheapest_print = Some((nft, print_lamports));
+        break;
+    }
+    if let Some((print_nft, print_lamports)) = cheapest_print {
+        let sol_amount = lamports_to_sol(print_lamports);
         let usd_amount = sol_amount * usd_per_sol;
-
         links.push(LinkedAction {
-            label: format!("Buy for {SOL_SYMBOL}{sol_amount:.2} (~${usd_amount:.2})"),
+            action_type: Some(LinkedActionType::Transaction),
+            label: format!(
+                "Buy a print of {} for {SOL_SYMBOL}{sol_amount:.2} (~${usd_amount:.2})",
+                print_nft.nft_name
+            ),
             href: uri!(blink_nft_post(
-                token_id = token_id,
+                token_id = &print_nft.token_id,
                 action = "buy-print",
-                price = _
+                price = _,
+                quantity = Some(1)
             ))
             .to_string(),
             parameters: vec![],
         });
     }
-    // finally, allow placing an offer on the nft
-    else {
-        links.push(LinkedAction {
-            label: "Place offer".to_string(),
-            href: uri!(blink_nft_post(
-                token_id = token_id,
-                action = "place-offer",
-                price = _
-            ))
-            .to_string(),
-            parameters: vec![ActionParameter {
-                parameter_type: "number".to_string(),
-                name: "price".to_string(),
-                label: "Custom amount".to_string(),
-                required: true,
-                min: Some(0.01),
-                ..ActionParameter::default()
-            }],
+
+    let mut auctions = nfts
+        .iter()
+        .filter(|nft| nft.auction.is_some() && !is_auction_ended(nft))
+        .filter_map(|nft| Some((nft, get_minimum_bid(nft)?)))
+        .collect::<Vec<_>>();
+    auctions.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    for (nft, minimum_bid) in auctions.into_iter().take(MAX_LISTED_AUCTIONS) {
+        // clients without post links can't open the nft blink, so they bid the minimum directly
+        links.push(match version.supports(ActionVersion::LINKED_ACTION_TYPES) {
+            true => LinkedAction {
+                action_type: Some(LinkedActionType::Post),
+                label: format!("Bid on {} from {SOL_SYMBOL}{minimum_bid:.2}", nft.nft_name),
+                href: uri!(blink_collection_open_post(
+                    collection_id = collection_id,
+                    token_id = &nft.token_id
+                ))
+                .to_string(),
+                parameters: vec![],
+            },
+            false => LinkedAction {
+                action_type: Some(LinkedActionType::Transaction),
+                label: format!("Bid {SOL_SYMBOL}{minimum_bid:.2} on {}", nft.nft_name),
+                href: uri!(blink_nft_post(
+                    token_id = &nft.token_id,
+                    action = "bid",
+                    price = Some(minimum_bid),
+                    quantity = _
+                ))
+                .to_string(),
+                parameters: vec![],
+            },
         });
     }
 
-    // TODO: include product price in sol and usd in the action label
     ActionGetResponse {
-        blockchain_id,
-        // TODO: add url for products with missing image
-        icon: get_image_for_nft(&nft).unwrap_or_default(),
-        title: nft.nft_name,
-        // TODO: fetch nft description from chain
-        description: [
-            match das_nft_future.await {
-                Ok(das_nft) => das_nft.result.content.metadata.description,
-                Err(e) => format!("DAS error: {e}"),
-            },
-            "".to_string(),
-            format!("nft by {}", artist_name),
-        ]
-        .join("\n"),
+        blockchain_ids,
+        icon: collection.image_url.unwrap_or_default(),
+        title: collection.name,
+        description: description.join("\n"),
+        label: "View collection".to_string(),
+        disabled: links.is_empty(),
         links: links.into(),
         ..ActionGetResponse::default()
     }
 }
 
-fn get_image_for_nft(nft: &SingleNftResponse) -> Option<String> {
-    let image_url = match nft.asset_type.as_ref() {
-        _ if nft.asset_type.as_str().starts_with("video") => nft.cover_image_url.clone(),
-        _ if nft.asset_type.as_str().starts_with("audio") => nft.cover_image_url.clone(),
-        "vr" => nft.cover_image_url.clone(),
-        _ => Some(nft.asset_url.clone()),
-    };
-    image_url.map(|url| format!("https://cdn.helius-rpc.com/cdn-cgi/image/quality=75/{url}"))
-}
-
-#[post(
-    "/nft/<token_id>/<action>?<price>",
-    format = "application/json",
-    data = "<request>",
-    rank = 1
-)]
-pub async fn blink_nft_post(
+// opens the blink of a single nft from the collection in place
+#[post("/collection/<collection_id>/open/<token_id>")]
+pub async fn blink_collection_open_post(
+    collection_id: &str,
     token_id: &str,
-    action: &str,
-    price: Option<f64>,
-    request: Json<ActionPostRequest<'_, Option<NftActionBlinkData>>>,
-) -> Result<ActionPostResponse, ErrorResponse> {
-    let NftActionBlinkData {
-        price: request_price,
-    } = match &request.data {
-        Some(data) => data,
-        None => &NftActionBlinkData::default(),
-    };
+) -> Result<ActionPostResponse, BlinkError> {
+    get_nfts_by_collection(collection_id)
+        .map_err(BlinkError::not_found(format!("collection {collection_id}")))?
+        .iter()
+        .find(|nft| nft.token_id == token_id)
+        .ok_or_else(|| BlinkError::NotFound {
+            resource: format!("nft {token_id}"),
+            details: format!("{token_id} is not part of collection {collection_id}"),
+        })?;
 
-    let effective_price = price.and(*request_price);
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Post,
+        message: None,
+        links: Some(ActionPostLinks {
+            next: NextAction::inline(blink_nft_get(token_id).await),
+        }),
+    })
+}
 
-    // TODO: implement transaction creation for all action types
-    let response = match action {
-        "buy-print" => {
-            let prints = create_print(
-                token_id,
-                Json(PrintEditionRequest {
-                    buyer: request.account,
-                    count: Some(1),
-                    editions: None,
-                }),
-            )
-            .await?;
-            let print_info = &prints[0];
-
-            ActionPostResponse {
-                blockchain_id: get_blockchain_id(),
-                transaction: print_info.transaction.clone(),
-                message: Some(format!(
-                    "Minting Print Edition #{}",
-                    print_info.edition_number
-                )),
-                links: Some(ActionPostLinks {
-                    next: NextAction::Post {
-                        href: uri!(blink_nft_index_print_post(
-                            token_id = &print_info.edition_mint,
-                        ))
-                        .to_string(),
-                    },
-                }),
-            }
-        }
-        "buy" | "bid" | "place-offer" => {
-            return Err(format!("action not implemented: {action}").into())
+// editions bought together are indexed together, `token_ids` is a comma separated list.
+// the mint transaction and editions are checked against the chain, so replaying the callback
+// only returns the card
+#[post("/nft/index-print/<token_ids>?<token_id>", data = "<request>", rank = 0)]
+pub async fn blink_nft_index_print_post(
+    token_ids: &str,
+    // the master edition, only set on callbacks that can show the pending card
+    token_id: Option<&str>,
+    request: Json<ActionPostRequest<'_>>,
+) -> Result<ActionGetResponse, BlinkError> {
+    let signature = request
+        .signature
+        .ok_or_else(|| BlinkError::InvalidRequest("missing signature".to_string()))?;
+    let edition_mints = token_ids.split(',').collect::<Vec<_>>();
+
+    // the callback can arrive before the rpc node has the mint transaction, the buyer can check
+    // again from the pending card then
+    if let Some(token_id) = token_id {
+        let signatures = find_reference_signatures(edition_mints[0])
+            .await
+            .map_err(BlinkError::Rpc)?;
+        if signatures.is_empty() {
+            let nft = get_single_nft_response(token_id)
+                .map_err(BlinkError::not_found(format!("nft {token_id}")))?;
+            return Ok(print_pending_card(&nft, token_ids));
         }
-        _ => return Err(format!("unknown blink action: {action}").into()),
-    };
+    }
 
-    Ok(response)
+    confirm_print_transaction(signature, request.account, &edition_mints)
+        .await
+        .map_err(BlinkError::InvalidPayment)?;
+    index_prints(token_ids, request.account).await
 }
 
-#[post("/nft/index-print/<token_id>", data = "<request>", rank = 0)]
-pub async fn blink_nft_index_print_post(
+// polled from the pending card, indexes the editions once their mint transaction landed
+#[post("/nft/<token_id>/prints/<token_ids>/status", data = "<request>")]
+pub async fn blink_nft_print_status_post(
     token_id: &str,
+    token_ids: &str,
     request: Json<ActionPostRequest<'_>>,
-) -> Result<ActionGetResponse, ErrorResponse> {
-    let das_nft = get_nft_from_das(token_id)
+) -> Result<ActionPostResponse, BlinkError> {
+    let edition_mints = token_ids.split(',').collect::<Vec<_>>();
+    // nothing touches a new edition mint before the transaction that mints it, but anyone can
+    // after, so every transaction is checked until one minted the editions
+    let signatures = find_reference_signatures(edition_mints[0])
         .await
-        .map_err(|e| format!("DAS error: {e}"))?;
-
-    let parent_nft = match das_nft.result.supply.master_edition_mint {
-        Some(parent_nft) => get_single_nft_response(&parent_nft),
-        None => Err(format!("error: nft is not a print {token_id}")),
-    }?;
+        .map_err(BlinkError::Rpc)?;
+    let mut minted = false;
+    let mut errors = vec![];
+    for signature in &signatures {
+        match confirm_print_transaction(signature, request.account, &edition_mints).await {
+            Ok(_) => {
+                minted = true;
+                break;
+            }
+            Err(e) => errors.push(e),
+        }
+    }
 
-    let new_nft = NewSingleNft {
-        owner_id: request.account,
-        token_id,
-        minter_id: request.account,
-        collection_id: parent_nft.collection_id.clone(),
-        nft_name: &parent_nft.nft_name,
-        minted_on_foster: true,
-        views: 0,
-        likes: 0,
-        shares: 0,
-        saves: 0,
-        categories: &parent_nft.categories,
-        asset_url: &parent_nft.asset_url,
-        asset_type: &parent_nft.asset_type,
-        cover_image_url: parent_nft.cover_image_url.as_deref(),
-        royalties: &parent_nft.royalties,
-        parent_nft: Some(&parent_nft.token_id),
-        max_supply: das_nft
-            .result
-            .supply
-            .print_max_supply
-            .map(|max_supply| max_supply.into()),
-        edition: das_nft
-            .result
-            .supply
-            .edition_number
-            .unwrap_or_default()
-            .into(),
+    let card = match (minted, errors.is_empty()) {
+        (true, _) => index_prints(token_ids, request.account).await?,
+        (false, true) => print_pending_card(
+            &get_single_nft_response(token_id)
+                .map_err(BlinkError::not_found(format!("nft {token_id}")))?,
+            token_ids,
+        ),
+        (false, false) => return Err(BlinkError::InvalidPayment(errors.join(", "))),
     };
 
-    // when editions are minted, notify artist
-    foster_notification::mint_edition(&new_nft);
-    mint_single_nft(new_nft);
+    Ok(ActionPostResponse {
+        blockchain_ids: get_blockchain_ids(),
+        kind: ActionPostResponseKind::Post,
+        message: None,
+        links: Some(ActionPostLinks {
+            next: NextAction::inline(card),
+        }),
+    })
+}
+
+// callers check the mint transaction first
+async fn index_prints(token_ids: &str, account: &str) -> Result<ActionGetResponse, BlinkError> {
+    let edition_mints = token_ids.split(',').collect::<Vec<_>>();
+    let mut indexed: Vec<(SingleNftResponse, String)> = vec![];
+    for token_id in edition_mints {
+        let das_nft = get_nft_from_das(token_id)
+            .await
+            .map_err(|e| BlinkError::Das(e.to_string()))?;
+        if das_nft.result.ownership.owner != account {
+            return Err(BlinkError::InvalidPayment(format!(
+                "{token_id} is owned by {}, not {}",
+                das_nft.result.ownership.owner, account
+            )));
+        }
+
+        let parent_nft = match &das_nft.result.supply.master_edition_mint {
+            Some(parent_nft) => get_single_nft_response(parent_nft)
+                .map_err(BlinkError::not_found(format!("nft {parent_nft}"))),
+            None => Err(BlinkError::InvalidRequest(format!("nft {token_id} is not a print"))),
+        }?;
+        if parent_nft.master_edition.is_none() {
+            return Err(BlinkError::InvalidRequest(format!(
+                "nft {} is not a master edition",
+                parent_nft.token_id
+            )));
+        }
+        if let Some((previous_parent, _)) = indexed.last() {
+            if previous_parent.token_id != parent_nft.token_id {
+                return Err(BlinkError::InvalidRequest(format!(
+                    "{token_ids} are not prints of the same nft"
+                )));
+            }
+        }
+
+        if get_single_nft_response(token_id).is_ok() {
+            indexed.push((parent_nft, das_nft.result.content.metadata.description));
+            continue;
+        }
+
+        let new_nft = NewSingleNft {
+            owner_id: account,
+            token_id,
+            minter_id: account,
+            collection_id: parent_nft.collection_id.clone(),
+            nft_name: &parent_nft.nft_name,
+            minted_on_foster: true,
+            views: 0,
+            likes: 0,
+            shares: 0,
+            saves: 0,
+            categories: &parent_nft.categories,
+            asset_url: &parent_nft.asset_url,
+            asset_type: &parent_nft.asset_type,
+            cover_image_url: parent_nft.cover_image_url.as_deref(),
+            royalties: &parent_nft.royalties,
+            parent_nft: Some(&parent_nft.token_id),
+            max_supply: das_nft
+                .result
+                .supply
+                .print_max_supply
+                .map(|max_supply| max_supply.into()),
+            edition: das_nft
+                .result
+                .supply
+                .edition_number
+                .unwrap_or_default()
+                .into(),
+        };
+
+        // when editions are minted, notify artist
+        foster_notification::mint_edition(&new_nft);
+        mint_single_nft(new_nft);
+
+        indexed.push((parent_nft, das_nft.result.content.metadata.description));
+    }
 
+    let count = indexed.len();
+    let (parent_nft, description) = indexed
+        .pop()
+        .ok_or_else(|| BlinkError::InvalidRequest("no editions to index".to_string()))?;
     Ok(ActionGetResponse {
-        blockchain_id: get_blockchain_id(),
+        blockchain_ids: get_blockchain_ids(),
         // TODO: add url for products with missing image
         icon: get_image_for_nft(&parent_nft).unwrap_or_default(),
         title: parent_nft.nft_name,
-        description: das_nft.result.content.metadata.description,
-        label: "NFT bought successfully!".to_string(),
+        description,
+        label: match count {
+            1 => "NFT bought successfully!".to_string(),
+            count => format!("{count} prints bought successfully!"),
+        },
         ..ActionGetResponse::default()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_price_without_price() {
+        assert!(matches!(resolve_price(None, None), Ok(None)));
+    }
+
+    #[test]
+    fn resolve_price_from_query() {
+        // fixed price links, e.g. buy now and minimum bid
+        assert!(matches!(resolve_price(Some(1.5), None), Ok(Some(price)) if price == 1.5));
+    }
+
+    #[test]
+    fn resolve_price_from_data() {
+        assert!(matches!(resolve_price(None, Some(2.25)), Ok(Some(price)) if price == 2.25));
+    }
+
+    #[test]
+    fn resolve_price_from_query_and_data() {
+        // custom price links send the same input in the query and the data
+        assert!(matches!(resolve_price(Some(0.1), Some(0.1)), Ok(Some(price)) if price == 0.1));
+        // a difference below one lamport is float noise
+        assert!(matches!(
+            resolve_price(Some(0.1), Some(0.1 + 1e-12)),
+            Ok(Some(price)) if price == 0.1
+        ));
+    }
+
+    #[test]
+    fn resolve_quantity_from_query_or_data() {
+        assert!(matches!(resolve_quantity(None, None), Ok(None)));
+        assert!(matches!(resolve_quantity(Some(1), None), Ok(Some(1))));
+        assert!(matches!(resolve_quantity(None, Some(3)), Ok(Some(3))));
+        assert!(matches!(resolve_quantity(Some(3), Some(3)), Ok(Some(3))));
+    }
+
+    #[test]
+    fn resolve_quantity_mismatch() {
+        assert!(matches!(
+            resolve_quantity(Some(1), Some(3)),
+            Err(BlinkError::QuantityMismatch { query: 1, data: 3 })
+        ));
+    }
+
+    #[test]
+    fn resolve_price_mismatch() {
+        assert!(matches!(
+            resolve_price(Some(1.0), Some(2.0)),
+            Err(BlinkError::PriceMismatch { query, data }) if query == 1.0 && data == 2.0
+        ));
+    }
+
+    fn mounted_route(name: &str) -> Route {
+        rocket::build()
+            .mount("/blinks", routes())
+            .routes()
+            .find(|route| route.name.as_deref() == Some(name))
+            .cloned()
+            .unwrap()
+    }
+
+    #[test]
+    fn api_path_matches_route_segments() {
+        let route = mounted_route("blink_nft_get");
+        assert!(api_path_matches_route("/v1/blinks/nft/abc", &route));
+        // the query is not part of the match
+        assert!(api_path_matches_route("/v1/blinks/nft/abc?price=1", &route));
+        assert!(!api_path_matches_route("/v1/blinks/collection/abc", &route));
+        assert!(!api_path_matches_route("/v1/blinks/nft", &route));
+        assert!(!api_path_matches_route("/v1/blinks/nft/abc/offers", &route));
+    }
+
+    #[test]
+    fn api_path_matches_route_wildcards() {
+        assert!(api_path_matches_route("/v1/blinks/nft/**", &mounted_route("blink_nft_get")));
+        assert!(api_path_matches_route(
+            "/v1/blinks/nft/abc/offers",
+            &mounted_route("blink_preflight")
+        ));
+    }
+}
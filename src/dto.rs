@@ -2,7 +2,7 @@
 #[serde(rename_all = "camelCase")]
 pub struct ActionGetResponse {
     #[serde(skip)]
-    pub blockchain_id: String,
+    pub blockchain_ids: BlockchainIds,
     #[serde(rename = "type")]
     pub action_type: BlinkActionType,
     pub icon: String,
@@ -16,11 +16,199 @@ pub struct ActionGetResponse {
     pub error: Option<ActionError>,
 }
 
+impl ActionGetResponse {
+    // drops everything the client's spec version can't handle
+    pub fn downgrade(&mut self, version: ActionVersion) {
+        if version < ActionVersion::TYPED_PARAMETERS {
+            self.links
+                .actions
+                .iter_mut()
+                .flat_map(|action| action.parameters.iter_mut())
+                .for_each(|parameter| parameter.omit_type = true);
+        }
+        if version < ActionVersion::LINKED_ACTION_TYPES {
+            self.links.actions.retain(|action| {
+                matches!(action.action_type, None | Some(LinkedActionType::Transaction))
+            });
+            self.links
+                .actions
+                .iter_mut()
+                .for_each(|action| action.action_type = None);
+        }
+    }
+}
+
 impl<'r> response::Responder<'r, 'static> for ActionGetResponse {
-    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
-        Response::build_from(Json::respond_to(Json(&self), req)?)
-            .raw_header("x-blockchain-ids", self.blockchain_id)
-            .ok()
+    fn respond_to(mut self, req: &'r Request<'_>) -> response::Result<'static> {
+        let version = ActionVersion::from_request_headers(req);
+        self.downgrade(version);
+
+        let mut response = Response::build_from(Json::respond_to(Json(&self), req)?);
+        response.raw_header(ActionVersion::HEADER, version.to_string());
+        if !self.blockchain_ids.is_empty() {
+            response.raw_header("X-Blockchain-Ids", self.blockchain_ids.to_string());
+        }
+        response.ok()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainId {
+    SolanaMainnet,
+    SolanaDevnet,
+    SolanaTestnet,
+    SolanaLocalnet,
+}
+
+impl ChainId {
+    pub const SOLANA_NAMESPACE: &'static str = "solana";
+
+    pub fn from_network(network: &str) -> Result<Self, String> {
+        match network {
+            "mainnet" | "mainnet-beta" => Ok(Self::SolanaMainnet),
+            "devnet" => Ok(Self::SolanaDevnet),
+            "testnet" => Ok(Self::SolanaTestnet),
+            "localnet" | "localhost" => Ok(Self::SolanaLocalnet),
+            _ => Err(format!("unknown solana network: {network}")),
+        }
+    }
+
+    // CAIP-2 reference, the first 32 characters of the genesis hash
+    pub fn reference(&self) -> &'static str {
+        match self {
+            Self::SolanaMainnet => "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp",
+            Self::SolanaDevnet => "EtWTRABZaYq6iMfeYKouRu166VU2xqa1",
+            Self::SolanaTestnet => "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3z",
+            // local validators have a random genesis hash
+            Self::SolanaLocalnet => "localnet",
+        }
+    }
+}
+
+impl std::fmt::Display for ChainId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", Self::SOLANA_NAMESPACE, self.reference())
+    }
+}
+
+impl std::str::FromStr for ChainId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (namespace, reference) = s
+            .split_once(':')
+            .ok_or_else(|| format!("invalid CAIP-2 chain id: {s}"))?;
+        if namespace != Self::SOLANA_NAMESPACE {
+            return Err(format!("unsupported chain namespace: {namespace}"));
+        }
+        [
+            Self::SolanaMainnet,
+            Self::SolanaDevnet,
+            Self::SolanaTestnet,
+            Self::SolanaLocalnet,
+        ]
+        .into_iter()
+        .find(|chain_id| chain_id.reference() == reference)
+        .ok_or_else(|| format!("unknown solana chain reference: {reference}"))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockchainIds(pub Vec<ChainId>);
+
+impl BlockchainIds {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<ChainId> for BlockchainIds {
+    fn from(chain_id: ChainId) -> Self {
+        Self(vec![chain_id])
+    }
+}
+
+impl std::fmt::Display for BlockchainIds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chain_ids = self.0.iter().map(ChainId::to_string).collect::<Vec<_>>();
+        write!(f, "{}", chain_ids.join(", "))
+    }
+}
+
+// actions spec version, only major and minor versions change the response format
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ActionVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ActionVersion {
+    pub const HEADER: &'static str = "X-Action-Version";
+    pub const ACCEPT_HEADER: &'static str = "X-Accept-Action-Version";
+
+    pub const V2_0: Self = Self::new(2, 0);
+    pub const V2_1: Self = Self::new(2, 1);
+    pub const V2_2: Self = Self::new(2, 2);
+    pub const V2_4: Self = Self::new(2, 4);
+
+    pub const CURRENT: Self = Self::V2_4;
+    // clients that don't advertise a version get the responses we served before versioning
+    pub const DEFAULT: Self = Self::V2_1;
+
+    pub const TYPED_PARAMETERS: Self = Self::V2_1;
+    pub const ACTION_CHAINING: Self = Self::V2_1;
+    pub const INLINE_NEXT_ACTION: Self = Self::V2_2;
+    pub const LINKED_ACTION_TYPES: Self = Self::V2_4;
+
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self { major, minor }
+    }
+
+    // version of the response we send, never newer than what we implement
+    pub fn from_request_headers(req: &Request<'_>) -> Self {
+        req.headers()
+            .get_one(Self::ACCEPT_HEADER)
+            .and_then(|version| version.parse::<Self>().ok())
+            .unwrap_or(Self::DEFAULT)
+            .min(Self::CURRENT)
+    }
+
+    pub fn supports(&self, feature: Self) -> bool {
+        *self >= feature
+    }
+}
+
+impl Default for ActionVersion {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl std::fmt::Display for ActionVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl std::str::FromStr for ActionVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('.').map(|part| part.parse::<u16>());
+        match (parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor))) => Ok(Self::new(major, minor)),
+            (Some(Ok(major)), None) => Ok(Self::new(major, 0)),
+            _ => Err(format!("invalid action version: {s}")),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> rocket::request::FromRequest<'r> for ActionVersion {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> rocket::request::Outcome<Self, Self::Error> {
+        rocket::request::Outcome::Success(Self::from_request_headers(req))
     }
 }
 
@@ -58,24 +246,262 @@ impl From<Vec<LinkedAction>> for LinkActions {
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LinkedAction {
+    // omitted for clients that predate linked action types, which treat every link as a
+    // transaction
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub action_type: Option<LinkedActionType>,
     pub label: String,
     pub href: String,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub parameters: Vec<ActionParameter>,
 }
 
-#[derive(Default, Serialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkedActionType {
+    #[default]
+    Transaction,
+    Message,
+    Post,
+    ExternalLink,
+}
+
+#[derive(Default)]
 pub struct ActionParameter {
-    #[serde(rename = "type", skip_serializing_if = "String::is_empty")]
-    pub parameter_type: String,
+    pub parameter_type: Option<ActionParameterType>,
     pub name: String,
     pub label: String,
     pub required: bool,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub options: Vec<ActionParameterOption>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    // dates are bounded by their unix timestamp, and sent to clients in the input's format
     pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub pattern: Option<String>,
+    pub pattern_description: Option<String>,
+    // set by `downgrade` for clients that predate typed parameters, the type is still validated
+    pub omit_type: bool,
+}
+
+impl ActionParameter {
+    // rejects combinations of attributes that clients can't render
+    pub fn validate(&self) -> Result<(), String> {
+        use ActionParameterType::*;
+
+        let name = &self.name;
+        let parameter_type = self.parameter_type.unwrap_or_default();
+
+        match parameter_type {
+            Select | Radio | Checkbox if self.options.is_empty() => {
+                return Err(format!("parameter {name}: {parameter_type} requires options"));
+            }
+            Select | Radio | Checkbox => {}
+            _ if !self.options.is_empty() => {
+                return Err(format!("parameter {name}: {parameter_type} can't have options"));
+            }
+            _ => {}
+        }
+        if matches!(parameter_type, Select | Radio)
+            && self.options.iter().filter(|option| option.selected).count() > 1
+        {
+            return Err(format!(
+                "parameter {name}: {parameter_type} can't have multiple selected options"
+            ));
+        }
+
+        if (self.min.is_some() || self.max.is_some()) && matches!(parameter_type, Select | Radio) {
+            return Err(format!("parameter {name}: {parameter_type} can't have min or max"));
+        }
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min > max {
+                return Err(format!("parameter {name}: min {min} is greater than max {max}"));
+            }
+        }
+
+        if self.pattern.is_some() && !matches!(parameter_type, Text | Email | Url | Textarea) {
+            return Err(format!("parameter {name}: {parameter_type} can't have a pattern"));
+        }
+        if self.pattern_description.is_some() && self.pattern.is_none() {
+            return Err(format!("parameter {name}: pattern description requires a pattern"));
+        }
+
+        Ok(())
+    }
+}
+
+impl ActionParameter {
+    pub fn check_value(&self, value: Option<&str>) -> Result<(), String> {
+        use ActionParameterType::*;
+
+        let Some(value) = value else {
+            return match self.required {
+                true => Err("is required".to_string()),
+                false => Ok(()),
+            };
+        };
+
+        // numbers are bounded by value, text by length, checkboxes by selected options, and dates
+        // by timestamp
+        let bounded = match self.parameter_type.unwrap_or_default() {
+            Number => value
+                .parse::<f64>()
+                .map_err(|_| "must be a number".to_string())?,
+            Email => {
+                let (local, domain) = value.split_once('@').unwrap_or_default();
+                if local.is_empty() || !domain.contains('.') || domain.ends_with('.') {
+                    return Err("must be an email address".to_string());
+                }
+                value.chars().count() as f64
+            }
+            Url => {
+                if !(value.starts_with("https://") || value.starts_with("http://")) {
+                    return Err("must be a url".to_string());
+                }
+                value.chars().count() as f64
+            }
+            Date => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| "must be a date".to_string())?
+                .and_time(chrono::NaiveTime::MIN)
+                .and_utc()
+                .timestamp() as f64,
+            DatetimeLocal => chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+                .map_err(|_| "must be a date and time".to_string())?
+                .and_utc()
+                .timestamp() as f64,
+            Select | Radio | Checkbox => {
+                let selected = value.split(',').map(str::trim).collect::<Vec<_>>();
+                if let Some(invalid) = selected
+                    .iter()
+                    .find(|value| !self.options.iter().any(|option| option.value == **value))
+                {
+                    let options = self
+                        .options
+                        .iter()
+                        .map(|option| option.value.as_str())
+                        .collect::<Vec<_>>();
+                    return Err(format!("{invalid} is not one of {}", options.join(", ")));
+                }
+                if selected.len() > 1 && !matches!(self.parameter_type, Some(Checkbox)) {
+                    return Err("must be a single option".to_string());
+                }
+                selected.len() as f64
+            }
+            Text | Textarea => value.chars().count() as f64,
+        };
+
+        if let Some(min) = self.min.filter(|min| bounded < *min) {
+            let min = self.format_date_bound(min).unwrap_or_else(|| min.to_string());
+            return Err(format!("must be at least {min}"));
+        }
+        if let Some(max) = self.max.filter(|max| bounded > *max) {
+            let max = self.format_date_bound(max).unwrap_or_else(|| max.to_string());
+            return Err(format!("must be at most {max}"));
+        }
+
+        if let Some(pattern) = &self.pattern {
+            let matches = regex::Regex::new(&format!("^(?:{pattern})$"))
+                .map_err(|e| format!("invalid pattern {pattern}: {e}"))?
+                .is_match(value);
+            if !matches {
+                return Err(self
+                    .pattern_description
+                    .clone()
+                    .unwrap_or_else(|| format!("must match {pattern}")));
+            }
+        }
+
+        Ok(())
+    }
+
+    // the bound in the format of the date input, `None` for other types
+    fn format_date_bound(&self, bound: f64) -> Option<String> {
+        let format = match self.parameter_type? {
+            ActionParameterType::Date => "%Y-%m-%d",
+            ActionParameterType::DatetimeLocal => "%Y-%m-%dT%H:%M",
+            _ => return None,
+        };
+        chrono::DateTime::from_timestamp(bound as i64, 0)
+            .map(|date| date.format(format).to_string())
+    }
+
+    fn bound_value(&self, bound: f64) -> serde_json::Value {
+        match self.format_date_bound(bound) {
+            Some(date) => date.into(),
+            None => bound.into(),
+        }
+    }
+}
+
+impl Serialize for ActionParameter {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ActionParameterRepr<'a> {
+            #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+            parameter_type: Option<ActionParameterType>,
+            name: &'a str,
+            label: &'a str,
+            required: bool,
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            options: &'a [ActionParameterOption],
+            #[serde(skip_serializing_if = "Option::is_none")]
+            min: Option<serde_json::Value>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            max: Option<serde_json::Value>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pattern: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pattern_description: Option<&'a str>,
+        }
+
+        self.validate().map_err(serde::ser::Error::custom)?;
+
+        ActionParameterRepr {
+            parameter_type: self.parameter_type.filter(|_| !self.omit_type),
+            name: &self.name,
+            label: &self.label,
+            required: self.required,
+            options: &self.options,
+            min: self.min.map(|min| self.bound_value(min)),
+            max: self.max.map(|max| self.bound_value(max)),
+            pattern: self.pattern.as_deref(),
+            pattern_description: self.pattern_description.as_deref(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ActionParameterType {
+    #[default]
+    Text,
+    Email,
+    Url,
+    Number,
+    Date,
+    DatetimeLocal,
+    Checkbox,
+    Radio,
+    Textarea,
+    Select,
+}
+
+impl std::fmt::Display for ActionParameterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parameter_type = match self {
+            Self::Text => "text",
+            Self::Email => "email",
+            Self::Url => "url",
+            Self::Number => "number",
+            Self::Date => "date",
+            Self::DatetimeLocal => "datetime-local",
+            Self::Checkbox => "checkbox",
+            Self::Radio => "radio",
+            Self::Textarea => "textarea",
+            Self::Select => "select",
+        };
+        write!(f, "{parameter_type}")
+    }
 }
 
 #[derive(Serialize)]
@@ -83,17 +509,70 @@ pub struct ActionParameter {
 pub struct ActionParameterOption {
     pub label: String,
     pub value: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub selected: bool,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ActionError {
     pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<ActionFieldError>,
 }
 
 impl From<String> for ActionError {
     fn from(message: String) -> Self {
-        Self { message }
+        Self {
+            message,
+            fields: vec![],
+        }
+    }
+}
+
+impl From<Vec<ActionFieldError>> for ActionError {
+    fn from(fields: Vec<ActionFieldError>) -> Self {
+        let message = fields
+            .iter()
+            .map(|field| format!("{}: {}", field.name, field.message))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Self {
+            message: format!("invalid parameters: {message}"),
+            fields,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionFieldError {
+    pub name: String,
+    pub message: String,
+}
+
+// checks submitted values against the parameters a GET advertised for the same action
+pub fn validate_action_parameters(
+    parameters: &[ActionParameter],
+    values: &std::collections::HashMap<&str, String>,
+) -> Result<(), ActionError> {
+    let fields = parameters
+        .iter()
+        .filter_map(|parameter| {
+            let value = values.get(parameter.name.as_str()).map(|value| value.trim());
+            parameter
+                .check_value(value.filter(|value| !value.is_empty()))
+                .err()
+                .map(|message| ActionFieldError {
+                    name: parameter.name.clone(),
+                    message,
+                })
+        })
+        .collect::<Vec<_>>();
+
+    match fields.is_empty() {
+        true => Ok(()),
+        false => Err(fields.into()),
     }
 }
 
@@ -102,6 +581,8 @@ impl From<String> for ActionError {
 pub struct ActionPostRequest<'a, T = Option<()>> {
     pub account: &'a str,
     pub signature: Option<&'a str>,
+    // returned as-is by the wallet after signing a message
+    pub state: Option<&'a str>,
     pub data: T,
 }
 
@@ -109,19 +590,104 @@ pub struct ActionPostRequest<'a, T = Option<()>> {
 #[serde(rename_all = "camelCase")]
 pub struct ActionPostResponse {
     #[serde(skip)]
-    pub blockchain_id: String,
-    pub transaction: String,
+    pub blockchain_ids: BlockchainIds,
+    #[serde(flatten)]
+    pub kind: ActionPostResponseKind,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub links: Option<ActionPostLinks>,
 }
 
+impl ActionPostResponse {
+    // drops everything the client's spec version can't handle
+    pub fn downgrade(&mut self, version: ActionVersion) {
+        if version < ActionVersion::ACTION_CHAINING {
+            self.links = None;
+        }
+        // an inline action can't be turned into a callback, so older clients just stop here
+        if version < ActionVersion::INLINE_NEXT_ACTION
+            && matches!(
+                self.links,
+                Some(ActionPostLinks {
+                    next: NextAction::Inline { .. }
+                })
+            )
+        {
+            self.links = None;
+        }
+        if let Some(ActionPostLinks {
+            next: NextAction::Inline { action },
+        }) = &mut self.links
+        {
+            action.downgrade(version);
+        }
+    }
+}
+
 impl<'r> response::Responder<'r, 'static> for ActionPostResponse {
-    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
-        Response::build_from(Json::respond_to(Json(&self), req)?)
-            .raw_header("x-blockchain-ids", self.blockchain_id)
-            .ok()
+    fn respond_to(mut self, req: &'r Request<'_>) -> response::Result<'static> {
+        let version = ActionVersion::from_request_headers(req);
+        self.downgrade(version);
+
+        let mut response = Response::build_from(Json::respond_to(Json(&self), req)?);
+        response.raw_header(ActionVersion::HEADER, version.to_string());
+        if !self.blockchain_ids.is_empty() {
+            response.raw_header("X-Blockchain-Ids", self.blockchain_ids.to_string());
+        }
+        response.ok()
+    }
+}
+
+// response shape for each linked action type
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+pub enum ActionPostResponseKind {
+    Transaction {
+        transaction: String,
+    },
+    Message {
+        data: SignMessageData,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        state: Option<String>,
+    },
+    Post,
+    ExternalLink {
+        #[serde(rename = "externalLink")]
+        external_link: String,
+    },
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignMessageData {
+    pub version: String,
+    pub domain: String,
+    pub address: String,
+    pub nonce: String,
+    pub issued_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement: Option<String>,
+}
+
+impl SignMessageData {
+    // the text wallets show and sign for this data
+    pub fn to_message_text(&self) -> String {
+        let mut message = format!(
+            "{} wants you to sign a message with your account:\n{}",
+            self.domain, self.address
+        );
+        if let Some(statement) = &self.statement {
+            message.push_str(&format!("\n\n{statement}"));
+        }
+        message.push('\n');
+        if let Some(chain_id) = &self.chain_id {
+            message.push_str(&format!("\nChain ID: {chain_id}"));
+        }
+        message.push_str(&format!("\nNonce: {}\nIssued At: {}", self.nonce, self.issued_at));
+        message
     }
 }
 
@@ -135,6 +701,15 @@ pub struct ActionPostLinks {
 #[serde(rename_all = "lowercase", tag = "type")]
 pub enum NextAction {
     Post { href: String },
+    Inline { action: Box<ActionGetResponse> },
+}
+
+impl NextAction {
+    pub fn inline(action: ActionGetResponse) -> Self {
+        Self::Inline {
+            action: Box::new(action),
+        }
+    }
 }
 
 // blink specific requests
@@ -142,14 +717,240 @@ pub enum NextAction {
 #[serde(rename_all = "camelCase")]
 pub struct MerchItemBlinkData<'a> {
     pub size: Option<&'a str>,
+    pub color: Option<&'a str>,
+    pub material: Option<&'a str>,
     #[serde(borrow)]
     pub email: Cow<'a, str>,
     #[serde(borrow)]
     pub address: Cow<'a, str>,
 }
 
+impl MerchItemBlinkData<'_> {
+    pub fn variant_attribute(&self, name: &str) -> Option<&str> {
+        match name {
+            "size" => self.size,
+            "color" => self.color,
+            "material" => self.material,
+            _ => None,
+        }
+    }
+}
+
+// a purchasable combination of product options, listed under `options["variants"]`
+#[derive(Clone, Default, Deserialize)]
+pub struct MerchProductVariant {
+    pub size: Option<String>,
+    pub color: Option<String>,
+    pub material: Option<String>,
+    // in cents, added to the selling price of the product
+    #[serde(default)]
+    pub price_delta: i32,
+    pub sku: Option<String>,
+    // variants without stock are not stock managed
+    pub stock: Option<i32>,
+}
+
+impl MerchProductVariant {
+    pub const ATTRIBUTES: [&'static str; 3] = ["size", "color", "material"];
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        match name {
+            "size" => self.size.as_deref(),
+            "color" => self.color.as_deref(),
+            "material" => self.material.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        Self::ATTRIBUTES
+            .iter()
+            .filter_map(|name| self.attribute(name))
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+}
+
+// binds the verified email to the signed message until it expires
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailVerificationState {
+    pub message: SignMessageData,
+    pub email: String,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
 #[derive(Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NftActionBlinkData {
     pub price: Option<f64>,
+    pub quantity: Option<u32>,
+}
+
+// actions.json discovery manifest
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionsJson {
+    pub rules: Vec<ActionRuleObject>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionRuleObject {
+    pub path_pattern: String,
+    pub api_path: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameter(parameter_type: ActionParameterType) -> ActionParameter {
+        ActionParameter {
+            parameter_type: Some(parameter_type),
+            name: "field".to_string(),
+            ..ActionParameter::default()
+        }
+    }
+
+    fn options(values: &[&str]) -> Vec<ActionParameterOption> {
+        values
+            .iter()
+            .map(|value| ActionParameterOption {
+                label: value.to_string(),
+                value: value.to_string(),
+                selected: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_value_required() {
+        let mut text = parameter(ActionParameterType::Text);
+        assert_eq!(text.check_value(None), Ok(()));
+        text.required = true;
+        assert_eq!(text.check_value(None), Err("is required".to_string()));
+    }
+
+    #[test]
+    fn check_value_email() {
+        let email = parameter(ActionParameterType::Email);
+        assert_eq!(email.check_value(Some("buyer@example.com")), Ok(()));
+        for invalid in ["buyer", "@example.com", "buyer@example", "buyer@example."] {
+            assert_eq!(
+                email.check_value(Some(invalid)),
+                Err("must be an email address".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn check_value_url() {
+        let url = parameter(ActionParameterType::Url);
+        assert_eq!(url.check_value(Some("https://example.com")), Ok(()));
+        assert_eq!(url.check_value(Some("http://example.com")), Ok(()));
+        assert_eq!(url.check_value(Some("ftp://example.com")), Err("must be a url".to_string()));
+    }
+
+    #[test]
+    fn check_value_number_bounds() {
+        let number = ActionParameter {
+            min: Some(1.0),
+            max: Some(10.0),
+            ..parameter(ActionParameterType::Number)
+        };
+        assert_eq!(number.check_value(Some("5")), Ok(()));
+        assert_eq!(number.check_value(Some("0.5")), Err("must be at least 1".to_string()));
+        assert_eq!(number.check_value(Some("11")), Err("must be at most 10".to_string()));
+        assert_eq!(number.check_value(Some("five")), Err("must be a number".to_string()));
+    }
+
+    #[test]
+    fn check_value_text_length() {
+        let text = ActionParameter {
+            max: Some(3.0),
+            ..parameter(ActionParameterType::Text)
+        };
+        assert_eq!(text.check_value(Some("abc")), Ok(()));
+        assert_eq!(text.check_value(Some("abcd")), Err("must be at most 3".to_string()));
+    }
+
+    #[test]
+    fn check_value_date_bounds() {
+        let date = ActionParameter {
+            // 2024-01-01
+            min: Some(1_704_067_200.0),
+            ..parameter(ActionParameterType::Date)
+        };
+        assert_eq!(date.check_value(Some("2024-01-02")), Ok(()));
+        assert_eq!(
+            date.check_value(Some("2023-12-31")),
+            Err("must be at least 2024-01-01".to_string())
+        );
+        assert_eq!(date.check_value(Some("01/02/2024")), Err("must be a date".to_string()));
+    }
+
+    #[test]
+    fn check_value_options() {
+        let checkbox = ActionParameter {
+            options: options(&["s", "m", "l"]),
+            max: Some(2.0),
+            ..parameter(ActionParameterType::Checkbox)
+        };
+        assert_eq!(checkbox.check_value(Some("s, m")), Ok(()));
+        assert_eq!(checkbox.check_value(Some("s,m,l")), Err("must be at most 2".to_string()));
+        assert_eq!(
+            checkbox.check_value(Some("xl")),
+            Err("xl is not one of s, m, l".to_string())
+        );
+
+        let select = ActionParameter {
+            options: options(&["s", "m", "l"]),
+            ..parameter(ActionParameterType::Select)
+        };
+        assert_eq!(select.check_value(Some("m")), Ok(()));
+        assert_eq!(select.check_value(Some("s,m")), Err("must be a single option".to_string()));
+    }
+
+    #[test]
+    fn check_value_pattern() {
+        let mut text = ActionParameter {
+            pattern: Some("[0-9]+".to_string()),
+            ..parameter(ActionParameterType::Text)
+        };
+        assert_eq!(text.check_value(Some("123")), Ok(()));
+        // the whole value has to match
+        assert_eq!(text.check_value(Some("12a")), Err("must match [0-9]+".to_string()));
+        text.pattern_description = Some("digits only".to_string());
+        assert_eq!(text.check_value(Some("12a")), Err("digits only".to_string()));
+    }
+
+    #[test]
+    fn validate_action_parameters_reports_every_field() {
+        let parameters = [
+            ActionParameter {
+                required: true,
+                ..parameter(ActionParameterType::Text)
+            },
+            ActionParameter {
+                name: "amount".to_string(),
+                min: Some(1.0),
+                ..parameter(ActionParameterType::Number)
+            },
+        ];
+        // blank values count as missing
+        let values = std::collections::HashMap::from([
+            ("field", "  ".to_string()),
+            ("amount", "0".to_string()),
+        ]);
+        let error = validate_action_parameters(&parameters, &values).unwrap_err();
+        assert_eq!(
+            error.message,
+            "invalid parameters: field: is required; amount: must be at least 1"
+        );
+        assert_eq!(error.fields.len(), 2);
+
+        let values = std::collections::HashMap::from([("field", "name".to_string())]);
+        assert!(validate_action_parameters(&parameters, &values).is_ok());
+    }
 }